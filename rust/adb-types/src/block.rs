@@ -0,0 +1,315 @@
+use std::io::{self, Read, Write};
+use std::ops::{Deref, DerefMut};
+use thiserror::Error;
+
+/// Error type for copying between `Block`s.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BlockError {
+    /// The destination has less room after its position than the source has
+    /// bytes remaining.
+    #[error("Cannot fit {needed} bytes in the {available} remaining")]
+    Overflow { needed: usize, available: usize },
+}
+
+/// A buffer of bytes with a position attribute, to allow sequential
+/// reads/writes for copying between `Block`s.
+///
+/// This is a port of the C++ `Block` in `original/types.h`. The C++ version
+/// avoids zero initialization as an optimization. The Rust version is backed
+/// by a `Vec<u8>`, and derefs to it for direct access to the data.
+#[derive(Debug, Default, Clone, Eq)]
+pub struct Block {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl Block {
+    /// Creates a new zero-filled `Block` of the given size.
+    pub fn new(size: usize) -> Self {
+        Self {
+            data: vec![0; size],
+            position: 0,
+        }
+    }
+
+    /// Creates a new `Block` containing a copy of `data`.
+    pub fn from_slice(data: &[u8]) -> Self {
+        Self {
+            data: data.to_vec(),
+            position: 0,
+        }
+    }
+
+    /// Returns whether the position has reached the end of the data.
+    pub fn is_full(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Returns the number of bytes between the position and the end of the
+    /// data.
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.position)
+    }
+
+    /// Copies as many bytes as possible from the position of `from` to the
+    /// position of `self`, advancing both positions.
+    ///
+    /// Returns the number of bytes copied.
+    pub fn fill_from(&mut self, from: &mut Block) -> usize {
+        let size = self.remaining().min(from.remaining());
+        self.data[self.position..self.position + size]
+            .copy_from_slice(&from.data[from.position..from.position + size]);
+        self.position += size;
+        from.position += size;
+        size
+    }
+
+    /// Copies all of the remaining bytes of `from` to the position of `self`
+    /// like `fill_from`, but fails if they don't all fit rather than copying
+    /// only part of them.
+    ///
+    /// On error, nothing is copied and neither position is moved.
+    pub fn try_fill_from(&mut self, from: &mut Block) -> Result<usize, BlockError> {
+        if from.remaining() > self.remaining() {
+            return Err(BlockError::Overflow {
+                needed: from.remaining(),
+                available: self.remaining(),
+            });
+        }
+        Ok(self.fill_from(from))
+    }
+
+    /// Extends the data with zeros so that at least `additional` bytes remain
+    /// after the position. The position itself is not moved.
+    ///
+    /// This supports building a header in place by writing at the position
+    /// and then rewinding.
+    pub fn ensure_remaining(&mut self, additional: usize) {
+        if self.remaining() < additional {
+            self.data.resize(self.position + additional, 0);
+        }
+    }
+
+    /// Resets the position to the start of the data.
+    pub fn rewind(&mut self) {
+        self.position = 0;
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Splits the data at `at`, returning the tail as a new `Block` with its
+    /// position at the start.
+    ///
+    /// Like `Vec::split_off`, `self` keeps the data before `at`. Its position
+    /// is clamped to the new end of the data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length of the data.
+    pub fn split_off(&mut self, at: usize) -> Block {
+        let tail = Block::from(self.data.split_off(at));
+        self.position = self.position.min(at);
+        tail
+    }
+
+    /// Removes all data and resets the position.
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.position = 0;
+    }
+
+    /// Returns a reader over the data, starting at the start of the data.
+    ///
+    /// The cursor has a position of its own, so reading from it leaves the
+    /// position of the block untouched.
+    pub fn cursor(&self) -> BlockCursor<'_> {
+        BlockCursor {
+            data: &self.data,
+            position: 0,
+        }
+    }
+}
+
+/// A reader over the data of a `Block`, created by `Block::cursor`.
+#[derive(Debug, Clone)]
+pub struct BlockCursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BlockCursor<'_> {
+    /// Returns the number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+}
+
+impl Read for BlockCursor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (&self.data[self.position..]).read(buf)?;
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl From<Vec<u8>> for Block {
+    fn from(data: Vec<u8>) -> Self {
+        Self { data, position: 0 }
+    }
+}
+
+impl Deref for Block {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.data
+    }
+}
+
+impl DerefMut for Block {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+}
+
+/// Appends to the end of the data, regardless of the position, e.g. to build
+/// a payload with `write!`. Writing never fails.
+impl Write for Block {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Like the C++ `Block::operator==`, only the data is compared, not the
+/// position.
+impl PartialEq for Block {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_from() {
+        let mut src = Block::from_slice(b"foobar");
+        let mut dst = Block::new(4);
+        assert_eq!(4, dst.fill_from(&mut src));
+        assert!(dst.is_full());
+        assert_eq!(2, src.remaining());
+        assert_eq!(b"foob", dst.as_slice());
+    }
+
+    #[test]
+    fn try_fill_from() {
+        let mut src = Block::from_slice(b"foo");
+        let mut dst = Block::new(4);
+        assert_eq!(Ok(3), dst.try_fill_from(&mut src));
+        assert_eq!(0, src.remaining());
+        assert_eq!(b"foo\0", dst.as_slice());
+
+        let mut src = Block::from_slice(b"bar");
+        assert_eq!(
+            Err(BlockError::Overflow {
+                needed: 3,
+                available: 1
+            }),
+            dst.try_fill_from(&mut src)
+        );
+        assert_eq!(3, src.remaining());
+        assert_eq!(3, dst.position());
+        assert_eq!(b"foo\0", dst.as_slice());
+    }
+
+    #[test]
+    fn ensure_remaining() {
+        let mut block = Block::default();
+        block.ensure_remaining(4);
+        assert_eq!(4, block.len());
+        assert_eq!(0, block.position());
+
+        assert_eq!(2, block.fill_from(&mut Block::from_slice(b"ab")));
+        block.ensure_remaining(4);
+        assert_eq!(6, block.len());
+        assert_eq!(2, block.position());
+        assert_eq!(4, block.remaining());
+
+        assert_eq!(4, block.fill_from(&mut Block::from_slice(b"cdef")));
+        assert!(block.is_full());
+        assert_eq!(b"abcdef", block.as_slice());
+
+        // Requesting less than what already remains doesn't shrink the data.
+        block.rewind();
+        block.ensure_remaining(1);
+        assert_eq!(6, block.len());
+    }
+
+    #[test]
+    fn write() {
+        let mut block = Block::from_slice(b"host");
+        block.write_all(b":").unwrap();
+        let version = 41;
+        write!(block, "version:{}", version).unwrap();
+        block.write_all(b"").unwrap();
+        assert_eq!(b"host:version:41", block.as_slice());
+        assert_eq!(15, block.len());
+        // The position isn't moved by writing.
+        assert_eq!(0, block.position());
+    }
+
+    #[test]
+    fn cursor() {
+        let mut block = Block::new(6);
+        block.fill_from(&mut Block::from_slice(b"foo"));
+
+        for _ in 0..2 {
+            let mut cursor = block.cursor();
+            let mut buf = [0u8; 4];
+            cursor.read_exact(&mut buf).unwrap();
+            assert_eq!(b"foo\0", &buf);
+            assert_eq!(4, cursor.position());
+            assert_eq!(2, cursor.remaining());
+
+            let mut rest = Vec::new();
+            cursor.read_to_end(&mut rest).unwrap();
+            assert_eq!(2, rest.len());
+            assert_eq!(0, cursor.remaining());
+        }
+        assert_eq!(3, block.position());
+    }
+
+    #[test]
+    fn split_off() {
+        let mut head = Block::from_slice(b"headerpayload");
+        head.fill_from(&mut Block::from_slice(b"headerpay"));
+        assert_eq!(9, head.position());
+
+        let tail = head.split_off(6);
+        assert_eq!(b"header", head.as_slice());
+        assert_eq!(6, head.position());
+        assert!(head.is_full());
+        assert_eq!(b"payload", tail.as_slice());
+        assert_eq!(0, tail.position());
+
+        // A position before the split point is kept.
+        head.rewind();
+        let tail = head.split_off(6);
+        assert_eq!(0, head.position());
+        assert!(tail.is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::packet::MAX_PAYLOAD;
+use crate::{Amessage, Apacket, Block, IoVector};
+use thiserror::Error;
+
+/// Error type for decoding a stream of packets.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum DecodeError {
+    /// A header claimed a payload larger than the decoder accepts.
+    #[error("Payload of {length} bytes exceeds the maximum of {max} bytes")]
+    PayloadTooLarge { length: u32, max: usize },
+}
+
+/// Decodes `Apacket`s from blocks of bytes split or merged on any
+/// boundaries.
+///
+/// Historically, adb expects packets to be transferred over USB with two
+/// transfers, one for the header and one for the payload. Buggy drivers and
+/// bridges can merge transfers (e.g. a payload and the next header), so this
+/// doesn't assume any relation between the blocks and the packets.
+///
+/// This is a port of the C++ `APacketReader` in
+/// `original/apacket_reader.cpp`. Rather than collecting all of the packets
+/// as bytes are added, packets are decoded one at a time by `next_packet`.
+#[derive(Debug)]
+pub struct FrameDecoder {
+    buffer: IoVector,
+    /// The header of the packet whose payload is being waited for.
+    header: Option<Amessage>,
+    max_payload: usize,
+    /// The error that made the stream undecodable, if any.
+    error: Option<DecodeError>,
+}
+
+impl FrameDecoder {
+    /// Creates a decoder accepting payloads of up to `MAX_PAYLOAD` bytes.
+    pub fn new() -> Self {
+        Self::with_max_payload(MAX_PAYLOAD)
+    }
+
+    /// Creates a decoder accepting payloads of up to `max` bytes.
+    pub fn with_max_payload(max: usize) -> Self {
+        Self {
+            buffer: IoVector::new(),
+            header: None,
+            max_payload: max,
+            error: None,
+        }
+    }
+
+    /// Adds received bytes to the decoder.
+    ///
+    /// Once decoding has failed, the bytes are dropped.
+    pub fn add_bytes(&mut self, block: Block) {
+        if self.error.is_none() {
+            self.buffer.append(block);
+        }
+    }
+
+    /// Returns the next complete packet, or `None` if more bytes are needed.
+    ///
+    /// The payload length is checked as soon as a header is complete, before
+    /// any of the payload is buffered. A header with an oversized payload
+    /// means the stream can't be decoded any further, since there's no way to
+    /// tell where the next packet starts. Like the C++ `APacketReader`, the
+    /// decoder then gives up: this and every later call return the same
+    /// error, and the connection should be closed.
+    pub fn next_packet(&mut self) -> Result<Option<Apacket>, DecodeError> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        let msg = match self.header {
+            Some(msg) => msg,
+            None => {
+                if self.buffer.size() < Amessage::HEADER_SIZE {
+                    return Ok(None);
+                }
+                let msg =
+                    Amessage::read_from(&self.buffer.take_front(Amessage::HEADER_SIZE).coalesce());
+                if msg.data_length as usize > self.max_payload {
+                    let error = DecodeError::PayloadTooLarge {
+                        length: msg.data_length,
+                        max: self.max_payload,
+                    };
+                    self.buffer.clear();
+                    self.error = Some(error.clone());
+                    return Err(error);
+                }
+                self.header = Some(msg);
+                msg
+            }
+        };
+
+        let len = msg.data_length as usize;
+        if self.buffer.size() < len {
+            return Ok(None);
+        }
+        self.header = None;
+        let payload = self.buffer.take_front(len).into_block();
+        Ok(Some(Apacket { msg, payload }))
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{A_CLSE, A_OKAY, A_WRTE};
+    use crate::AmessageBuilder;
+
+    fn to_bytes(packet: &Apacket) -> Vec<u8> {
+        let mut buf = Vec::new();
+        packet.write_to(&mut buf).unwrap();
+        buf
+    }
+
+    fn packets() -> Vec<Apacket> {
+        vec![
+            AmessageBuilder::new()
+                .command(A_OKAY)
+                .arg0(1)
+                .arg1(2)
+                .build(),
+            AmessageBuilder::new()
+                .command(A_WRTE)
+                .arg0(1)
+                .arg1(2)
+                .payload(Block::from_slice(b"hello world"))
+                .build(),
+            AmessageBuilder::new()
+                .command(A_CLSE)
+                .arg0(1)
+                .arg1(2)
+                .build(),
+        ]
+    }
+
+    fn decode_all(decoder: &mut FrameDecoder) -> Vec<Apacket> {
+        let mut result = Vec::new();
+        while let Some(packet) = decoder.next_packet().unwrap() {
+            result.push(packet);
+        }
+        result
+    }
+
+    #[test]
+    fn separate_transfers() {
+        let mut decoder = FrameDecoder::new();
+        let mut decoded = Vec::new();
+        for packet in packets() {
+            let bytes = to_bytes(&packet);
+            decoder.add_bytes(Block::from_slice(&bytes[..Amessage::HEADER_SIZE]));
+            decoded.extend(decode_all(&mut decoder));
+            decoder.add_bytes(Block::from_slice(&bytes[Amessage::HEADER_SIZE..]));
+            decoded.extend(decode_all(&mut decoder));
+        }
+        assert_eq!(packets(), decoded);
+    }
+
+    #[test]
+    fn merged_and_chopped() {
+        let stream: Vec<u8> = packets().iter().flat_map(to_bytes).collect();
+        for chunk_size in [1, 5, 24, 30, stream.len()] {
+            let mut decoder = FrameDecoder::new();
+            let mut decoded = Vec::new();
+            for chunk in stream.chunks(chunk_size) {
+                decoder.add_bytes(Block::from_slice(chunk));
+                decoded.extend(decode_all(&mut decoder));
+            }
+            assert_eq!(packets(), decoded, "chunk_size={}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn payload_too_large() {
+        let mut decoder = FrameDecoder::with_max_payload(1024);
+        let mut packet = AmessageBuilder::new().command(A_WRTE).build();
+        packet.msg.data_length = u32::MAX;
+        decoder.add_bytes(Block::from(to_bytes(&packet)));
+        assert_eq!(
+            Err(DecodeError::PayloadTooLarge {
+                length: u32::MAX,
+                max: 1024
+            }),
+            decoder.next_packet()
+        );
+
+        // The stream can't be resynchronized, so decoding keeps failing.
+        decoder.add_bytes(Block::from(to_bytes(&packets()[1])));
+        assert_eq!(
+            Err(DecodeError::PayloadTooLarge {
+                length: u32::MAX,
+                max: 1024
+            }),
+            decoder.next_packet()
+        );
+
+        // The maximum itself is allowed.
+        let mut decoder = FrameDecoder::with_max_payload(1024);
+        let packet = AmessageBuilder::new()
+            .command(A_WRTE)
+            .payload(Block::new(1024))
+            .build();
+        decoder.add_bytes(Block::from(to_bytes(&packet)));
+        assert_eq!(Ok(Some(packet)), decoder.next_packet());
+    }
+}
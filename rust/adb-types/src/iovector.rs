@@ -0,0 +1,926 @@
+use crate::packet::{AmessageBuilder, Apacket};
+use crate::Block;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+/// Error type for `IoVector` operations.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum IoVectorError {
+    /// More bytes were requested than the chain holds.
+    #[error("Requested {len} bytes, but only {size} are available")]
+    OutOfRange { len: usize, size: usize },
+}
+
+/// A chain of `Block`s, allowing data to be appended and split off the front
+/// without copying.
+///
+/// This is a port of the C++ `IOVector` in `original/types.h`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IoVector {
+    /// Total length of all of the blocks in the chain.
+    chain_length: usize,
+    /// Number of bytes at the start of the first block which have already
+    /// been consumed.
+    begin_offset: usize,
+    chain: VecDeque<Block>,
+}
+
+impl IoVector {
+    /// Creates a new, empty `IoVector`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an `IoVector` holding a copy of `data` in a single block.
+    pub fn from_slice(data: &[u8]) -> Self {
+        Self::from(Block::from_slice(data))
+    }
+
+    /// Creates an `IoVector` holding `data` in a single block, without
+    /// copying it.
+    pub fn from_vec(data: Vec<u8>) -> Self {
+        Self::from(Block::from(data))
+    }
+
+    /// Returns the data in the first block of the chain.
+    pub fn front_data(&self) -> &[u8] {
+        match self.chain.front() {
+            Some(block) => &block[self.begin_offset..],
+            None => &[],
+        }
+    }
+
+    /// Returns the size of the data in the first block of the chain.
+    pub fn front_size(&self) -> usize {
+        self.front_data().len()
+    }
+
+    /// Returns the total number of bytes in the chain.
+    pub fn size(&self) -> usize {
+        self.chain_length - self.begin_offset
+    }
+
+    /// Returns whether the chain contains no data.
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Returns the number of blocks in the chain.
+    pub fn block_count(&self) -> usize {
+        self.chain.len()
+    }
+
+    /// Removes all the data from the chain.
+    ///
+    /// Returns the last block so the caller can reuse its allocated capacity,
+    /// or it can be simply ignored.
+    pub fn clear(&mut self) -> Block {
+        self.chain_length = 0;
+        self.begin_offset = 0;
+        let last = self.chain.pop_back().unwrap_or_default();
+        self.chain.clear();
+        self.assert_invariants();
+        last
+    }
+
+    /// Removes the first `len` bytes from the chain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is greater than `size()`.
+    pub fn drop_front(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+        if len == self.size() {
+            self.clear();
+            return;
+        }
+        assert!(len <= self.size());
+
+        let mut dropped = 0;
+        while dropped < len {
+            let next = self.front_size();
+            if dropped + next <= len {
+                self.pop_front_block();
+                dropped += next;
+            } else {
+                self.begin_offset += len - dropped;
+                break;
+            }
+        }
+        self.assert_invariants();
+    }
+
+    /// Removes the first `len` bytes from the chain like `drop_front`, but
+    /// fails instead of panicking if `len` is greater than `size()`, leaving
+    /// the chain untouched.
+    pub fn try_drop_front(&mut self, len: usize) -> Result<(), IoVectorError> {
+        self.check_len(len)?;
+        self.drop_front(len);
+        Ok(())
+    }
+
+    /// Splits the first `len` bytes out of this chain like `take_front`, but
+    /// fails instead of panicking if `len` is greater than `size()`, leaving
+    /// the chain untouched.
+    pub fn try_take_front(&mut self, len: usize) -> Result<IoVector, IoVectorError> {
+        self.check_len(len)?;
+        Ok(self.take_front(len))
+    }
+
+    fn check_len(&self, len: usize) -> Result<(), IoVectorError> {
+        if len > self.size() {
+            return Err(IoVectorError::OutOfRange {
+                len,
+                size: self.size(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Passes the data of the chain to `f` one block at a time, from the
+    /// front, dropping as many bytes as `f` returns it consumed.
+    ///
+    /// Stops when the chain is empty, or when `f` consumes fewer bytes than
+    /// it was given, e.g. because it needs more data to parse a frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` returns more than the length of the slice it was given.
+    pub fn consume<F: FnMut(&[u8]) -> usize>(&mut self, mut f: F) {
+        while !self.is_empty() {
+            let front = self.front_data();
+            let len = front.len();
+            let consumed = f(front);
+            assert!(consumed <= len, "consumed {} of {} bytes", consumed, len);
+            self.drop_front(consumed);
+            if consumed < len {
+                break;
+            }
+        }
+    }
+
+    /// Splits the first `len` bytes out of this chain into its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is greater than `size()`.
+    pub fn take_front(&mut self, mut len: usize) -> IoVector {
+        if len == 0 {
+            return IoVector::new();
+        }
+        if len == self.size() {
+            return std::mem::take(self);
+        }
+        assert!(len <= self.size());
+
+        let mut res = IoVector::new();
+        // First move over the blocks that completely go into the other vector.
+        while self.front_size() <= len {
+            len -= self.front_size();
+            let begin_offset = std::mem::take(&mut self.begin_offset);
+            let block = self.chain.pop_front().unwrap();
+            self.chain_length -= block.len();
+            if res.is_empty() {
+                res.begin_offset = begin_offset;
+            }
+            res.append(block);
+        }
+
+        if len > 0 {
+            // What's left is a single block that needs to be split between
+            // `res` and `self`.
+            let front = &mut self.chain[0];
+            if self.begin_offset != 0 || len < front.len() / 2 {
+                // Copy the data out.
+                res.append(Block::from_slice(
+                    &front[self.begin_offset..self.begin_offset + len],
+                ));
+                self.begin_offset += len;
+            } else {
+                // Move the block out, and copy only its tail back in.
+                let tail = front.split_off(len);
+                let head = std::mem::replace(front, tail);
+                self.chain_length -= head.len();
+                res.append(head);
+            }
+        }
+        self.assert_invariants();
+        res.assert_invariants();
+        res
+    }
+
+    /// Adds a block to the end of the chain. Empty blocks are ignored.
+    pub fn append(&mut self, block: Block) {
+        if block.is_empty() {
+            return;
+        }
+        self.chain_length += block.len();
+        self.chain.push_back(block);
+        self.assert_invariants();
+    }
+
+    /// Adds a copy of `bytes` to the end of the chain as a new block.
+    pub fn append_bytes(&mut self, bytes: &[u8]) {
+        self.append(Block::from_slice(bytes));
+    }
+
+    /// Moves all of the data of `other` to the end of the chain.
+    ///
+    /// The blocks are moved without copying, except that the consumed data
+    /// at the start of the first block of `other` has to be trimmed if this
+    /// chain isn't empty.
+    pub fn append_iovector(&mut self, mut other: IoVector) {
+        if self.is_empty() {
+            *self = other;
+            self.assert_invariants();
+            return;
+        }
+        other.trim_front();
+        for block in other.chain {
+            self.append(block);
+        }
+    }
+
+    /// Adds a block to the end of the chain like `append`, unless that would
+    /// make `size()` exceed `max_size`, in which case the block is returned.
+    ///
+    /// This allows a bounded receive buffer to apply backpressure.
+    pub fn try_append(&mut self, block: Block, max_size: usize) -> Result<(), Block> {
+        if self.size() + block.len() > max_size {
+            return Err(block);
+        }
+        self.append(block);
+        Ok(())
+    }
+
+    /// Removes the already-consumed data at the start of the first block.
+    pub fn trim_front(&mut self) {
+        if self.begin_offset == 0 {
+            return;
+        }
+        let begin_offset = std::mem::take(&mut self.begin_offset);
+        if let Some(front) = self.chain.front_mut() {
+            front.drain(..begin_offset);
+        }
+        self.chain_length -= begin_offset;
+        self.assert_invariants();
+    }
+
+    /// Merges runs of adjacent blocks smaller than `threshold` bytes into
+    /// blocks of at least `threshold` bytes where possible, e.g. after many
+    /// small appends, so that vectored I/O deals with fewer slices.
+    ///
+    /// Blocks of `threshold` bytes or more are left untouched, and so is a
+    /// small block without small neighbours. The data and `size()` are
+    /// unchanged.
+    pub fn defragment(&mut self, threshold: usize) {
+        fn flush(run: &mut Vec<Block>, out: &mut VecDeque<Block>) {
+            match run.len() {
+                0 => {}
+                1 => out.push_back(run.pop().unwrap()),
+                _ => {
+                    let mut merged = Vec::with_capacity(run.iter().map(|b| b.len()).sum());
+                    for block in run.drain(..) {
+                        merged.extend_from_slice(&block);
+                    }
+                    out.push_back(Block::from(merged));
+                }
+            }
+        }
+
+        let mut out = VecDeque::with_capacity(self.chain.len());
+        let mut run = Vec::new();
+        let mut run_len = 0;
+        // The consumed data at the start of the first block is merged along
+        // with it, so that `begin_offset` stays valid.
+        for block in self.chain.drain(..) {
+            if block.len() >= threshold {
+                flush(&mut run, &mut out);
+                run_len = 0;
+                out.push_back(block);
+                continue;
+            }
+            run_len += block.len();
+            run.push(block);
+            if run_len >= threshold {
+                flush(&mut run, &mut out);
+                run_len = 0;
+            }
+        }
+        flush(&mut run, &mut out);
+        self.chain = out;
+        self.assert_invariants();
+    }
+
+    /// Copies all of the blocks into a single block.
+    pub fn coalesce(&self) -> Block {
+        let mut result = Vec::with_capacity(self.size());
+        for block in self.iter_slices() {
+            result.extend_from_slice(block);
+        }
+        Block::from(result)
+    }
+
+    /// Converts the chain into a single block, without copying if the chain
+    /// already consists of exactly one block.
+    pub(crate) fn into_block(mut self) -> Block {
+        if self.chain.len() == 1 && self.begin_offset == 0 {
+            let mut block = self.clear();
+            block.rewind();
+            return block;
+        }
+        self.coalesce()
+    }
+
+    /// Builds a packet with the data in this chain as its payload, and the
+    /// header filled in from `command`, `arg0` and `arg1`.
+    ///
+    /// The payload is moved into the packet without copying if the chain
+    /// already consists of exactly one block, and coalesced otherwise.
+    pub fn into_apacket(self, command: u32, arg0: u32, arg1: u32) -> Apacket {
+        AmessageBuilder::new()
+            .command(command)
+            .arg0(arg0)
+            .arg1(arg1)
+            .payload(self.into_block())
+            .build()
+    }
+
+    /// Returns whether the data in this chain is the same as in `other`,
+    /// regardless of how the data is split into blocks.
+    ///
+    /// Unlike `==`, which compares the structure of the chains, this compares
+    /// only the bytes, without coalescing either chain.
+    pub fn content_eq(&self, other: &IoVector) -> bool {
+        if self.size() != other.size() {
+            return false;
+        }
+        let mut ours = self.iter_slices();
+        let mut theirs = other.iter_slices();
+        let mut a: &[u8] = &[];
+        let mut b: &[u8] = &[];
+        loop {
+            if a.is_empty() {
+                match ours.next() {
+                    Some(next) => a = next,
+                    None => return b.is_empty() && theirs.next().is_none(),
+                }
+                continue;
+            }
+            if b.is_empty() {
+                match theirs.next() {
+                    Some(next) => b = next,
+                    None => return false,
+                }
+                continue;
+            }
+            let len = a.len().min(b.len());
+            if a[..len] != b[..len] {
+                return false;
+            }
+            a = &a[len..];
+            b = &b[len..];
+        }
+    }
+
+    /// Iterates over the live data in each block, skipping empty ones, e.g.
+    /// to pass the chain to a vectored write without coalescing it.
+    pub fn iter_slices(&self) -> impl Iterator<Item = &[u8]> {
+        self.chain
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                // Don't assume that `begin_offset` is within the first block,
+                // so that a fully consumed first block is skipped rather than
+                // panicking.
+                if i == 0 {
+                    block.get(self.begin_offset..).unwrap_or_default()
+                } else {
+                    &block[..]
+                }
+            })
+            .filter(|data| !data.is_empty())
+    }
+
+    /// Writes the chain to `w` as a little-endian `u32` length followed by
+    /// the data, without coalescing it.
+    pub fn write_length_prefixed<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let len = u32::try_from(self.size()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("IoVector of {} bytes is too large to prefix", self.size()),
+            )
+        })?;
+        w.write_all(&len.to_le_bytes())?;
+        for data in self.iter_slices() {
+            w.write_all(data)?;
+        }
+        Ok(())
+    }
+
+    /// Reads an `IoVector` written by `write_length_prefixed` from `r`, as a
+    /// single block.
+    pub fn read_length_prefixed<R: Read>(r: &mut R) -> io::Result<IoVector> {
+        let mut len = [0u8; 4];
+        r.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+        // Don't trust the length for the allocation, it grows with the data
+        // actually read.
+        let mut data = Vec::new();
+        r.take(len as u64).read_to_end(&mut data)?;
+        if data.len() != len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("Expected {} bytes, got {}", len, data.len()),
+            ));
+        }
+        Ok(IoVector::from_vec(data))
+    }
+
+    /// Checks the chain accounting, to turn a bookkeeping bug into an
+    /// immediate panic rather than silently corrupted data.
+    #[cfg(debug_assertions)]
+    fn assert_invariants(&self) {
+        let total: usize = self.chain.iter().map(|block| block.len()).sum();
+        assert_eq!(total, self.chain_length, "chain_length out of sync");
+        match self.chain.front() {
+            Some(front) => assert!(
+                self.begin_offset < front.len(),
+                "begin_offset {} past the first block of {} bytes",
+                self.begin_offset,
+                front.len()
+            ),
+            None => assert_eq!(0, self.begin_offset, "begin_offset in an empty chain"),
+        }
+        assert_eq!(self.chain_length - self.begin_offset, self.size());
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn assert_invariants(&self) {}
+
+    /// Drops the first block from the chain.
+    fn pop_front_block(&mut self) {
+        if let Some(block) = self.chain.pop_front() {
+            self.chain_length -= block.len();
+        }
+        self.begin_offset = 0;
+    }
+}
+
+impl From<Block> for IoVector {
+    fn from(block: Block) -> Self {
+        let mut iov = IoVector::new();
+        iov.append(block);
+        iov
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_block(s: &str) -> Block {
+        Block::from_slice(s.as_bytes())
+    }
+
+    #[test]
+    fn empty() {
+        let bc = IoVector::new();
+        assert_eq!(0, bc.coalesce().len());
+    }
+
+    #[test]
+    fn single_block() {
+        let block = Block::from(vec![b'x'; 100]);
+        let mut bc = IoVector::new();
+        bc.append(block.clone());
+        assert_eq!(100, bc.size());
+        assert_eq!(block, bc.coalesce());
+    }
+
+    #[test]
+    fn single_block_split() {
+        let mut bc = IoVector::new();
+        bc.append(create_block("foobar"));
+        let foo = bc.take_front(3);
+        assert_eq!(3, foo.size());
+        assert_eq!(3, bc.size());
+        assert_eq!(create_block("foo"), foo.coalesce());
+        assert_eq!(create_block("bar"), bc.coalesce());
+    }
+
+    #[test]
+    fn aligned_split() {
+        let mut bc = IoVector::new();
+        bc.append(create_block("foo"));
+        bc.append(create_block("bar"));
+        bc.append(create_block("baz"));
+        assert_eq!(9, bc.size());
+
+        assert_eq!(create_block("foo"), bc.take_front(3).coalesce());
+        assert_eq!(create_block("bar"), bc.take_front(3).coalesce());
+        assert_eq!(create_block("baz"), bc.take_front(3).coalesce());
+        assert_eq!(0, bc.size());
+    }
+
+    #[test]
+    fn misaligned_split() {
+        let mut bc = IoVector::new();
+        bc.append(create_block("foo"));
+        bc.append(create_block("bar"));
+        bc.append(create_block("baz"));
+        bc.append(create_block("qux"));
+        bc.append(create_block("quux"));
+
+        // Aligned left, misaligned right, across multiple blocks.
+        assert_eq!(create_block("foob"), bc.take_front(4).coalesce());
+        // Misaligned left, misaligned right, in one block.
+        assert_eq!(create_block("a"), bc.take_front(1).coalesce());
+        // Misaligned left, misaligned right, across two blocks.
+        assert_eq!(create_block("rba"), bc.take_front(3).coalesce());
+        // Misaligned left, misaligned right, across three blocks.
+        assert_eq!(create_block("zquxquu"), bc.take_front(7).coalesce());
+
+        assert_eq!(1, bc.size());
+        assert_eq!(create_block("x"), bc.coalesce());
+    }
+
+    #[test]
+    fn drop_front() {
+        let mut vec = IoVector::new();
+        vec.append(Block::from(vec![b'x'; 2]));
+        vec.append(Block::from(vec![b'y'; 1000]));
+        assert_eq!(2, vec.front_size());
+        assert_eq!(1002, vec.size());
+
+        vec.drop_front(1);
+        assert_eq!(1, vec.front_size());
+        assert_eq!(1001, vec.size());
+
+        vec.drop_front(1);
+        assert_eq!(1000, vec.front_size());
+        assert_eq!(1000, vec.size());
+    }
+
+    #[test]
+    fn take_front() {
+        let mut vec = IoVector::new();
+        assert!(vec.take_front(0).is_empty());
+
+        vec.append(Block::from(vec![b'x'; 2]));
+        assert_eq!(2, vec.size());
+        assert_eq!(1, vec.take_front(1).size());
+        assert_eq!(1, vec.size());
+        assert_eq!(1, vec.take_front(1).size());
+        assert_eq!(0, vec.size());
+    }
+
+    #[test]
+    fn try_append() {
+        let mut vec = IoVector::new();
+        vec.try_append(create_block("foo"), 6).unwrap();
+        vec.try_append(create_block("bar"), 6).unwrap();
+        assert_eq!(6, vec.size());
+
+        let block = vec.try_append(create_block("x"), 6).unwrap_err();
+        assert_eq!(create_block("x"), block);
+        assert_eq!(6, vec.size());
+
+        vec.drop_front(1);
+        vec.try_append(block, 6).unwrap();
+        assert_eq!(create_block("oobarx"), vec.coalesce());
+    }
+
+    #[test]
+    fn iter_slices() {
+        let mut vec = IoVector::new();
+        vec.append(create_block("foo"));
+        vec.append(create_block("bar"));
+        vec.append(create_block("baz"));
+        vec.drop_front(2);
+
+        let slices: Vec<&[u8]> = vec.iter_slices().collect();
+        assert_eq!(vec![&b"o"[..], b"bar", b"baz"], slices);
+        assert_eq!(vec.coalesce().to_vec(), slices.concat());
+
+        let io_slices: Vec<_> = vec.iter_slices().map(std::io::IoSlice::new).collect();
+        let mut written = Vec::new();
+        std::io::Write::write_vectored(&mut written, &io_slices).unwrap();
+        assert_eq!(b"obarbaz", written.as_slice());
+
+        assert_eq!(0, IoVector::new().iter_slices().count());
+    }
+
+    #[test]
+    fn try_drop_front() {
+        let mut vec = IoVector::new();
+        vec.append(create_block("foo"));
+        vec.append(create_block("bar"));
+        assert_eq!(
+            Err(IoVectorError::OutOfRange { len: 7, size: 6 }),
+            vec.try_drop_front(7)
+        );
+        assert_eq!(
+            Err(IoVectorError::OutOfRange {
+                len: usize::MAX,
+                size: 6
+            }),
+            vec.try_drop_front(usize::MAX)
+        );
+        assert_eq!(6, vec.size());
+
+        assert_eq!(Ok(()), vec.try_drop_front(4));
+        assert_eq!(create_block("ar"), vec.coalesce());
+    }
+
+    #[test]
+    fn try_take_front() {
+        let mut vec = IoVector::new();
+        vec.append(create_block("foo"));
+        vec.append(create_block("bar"));
+        assert_eq!(
+            Err(IoVectorError::OutOfRange { len: 7, size: 6 }),
+            vec.try_take_front(7)
+        );
+        assert_eq!(6, vec.size());
+
+        let front = vec.try_take_front(4).unwrap();
+        assert_eq!(create_block("foob"), front.coalesce());
+        assert_eq!(create_block("ar"), vec.coalesce());
+        assert!(vec.try_take_front(3).is_err());
+        assert_eq!(2, vec.try_take_front(2).unwrap().size());
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn append_bytes_and_iovector() {
+        let mut vec = IoVector::new();
+        vec.append_bytes(b"foo");
+        vec.append_bytes(b"");
+        vec.append(create_block("bar"));
+        assert_eq!(6, vec.size());
+        assert_eq!(2, vec.block_count());
+
+        let mut other = IoVector::new();
+        other.append_bytes(b"xxbaz");
+        other.append_bytes(b"qux");
+        other.drop_front(2);
+        vec.append_iovector(other);
+        assert_eq!(12, vec.size());
+        assert_eq!(4, vec.block_count());
+        assert_eq!(create_block("foobarbazqux"), vec.coalesce());
+
+        // Appending to an empty chain keeps the consumed data of the other.
+        vec.drop_front(4);
+        let mut empty = IoVector::new();
+        empty.append_iovector(vec.clone());
+        assert_eq!(vec, empty);
+        empty.append_iovector(IoVector::new());
+        assert_eq!(8, empty.size());
+        assert_eq!(create_block("arbazqux"), empty.coalesce());
+    }
+
+    #[test]
+    fn consume() {
+        let mut vec = IoVector::new();
+        vec.append(create_block("ab"));
+        vec.append(create_block("cdef"));
+        vec.append(create_block("ghi"));
+
+        // Consume in bites of up to 3 bytes, where a bite can't span blocks.
+        let mut bites = Vec::new();
+        vec.consume(|data| {
+            let len = data.len().min(3);
+            bites.push(data[..len].to_vec());
+            len
+        });
+        assert_eq!(vec![b"ab".to_vec(), b"cde".to_vec()], bites);
+        assert_eq!(create_block("fghi"), vec.coalesce());
+
+        // Consuming nothing stops right away.
+        let mut calls = 0;
+        vec.consume(|_| {
+            calls += 1;
+            0
+        });
+        assert_eq!(1, calls);
+        assert_eq!(4, vec.size());
+
+        let mut all = Vec::new();
+        vec.consume(|data| {
+            all.extend_from_slice(data);
+            data.len()
+        });
+        assert_eq!(b"fghi", all.as_slice());
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn trim_front() {
+        let mut vec = IoVector::new();
+        vec.append(Block::from(vec![b'x'; 2]));
+        assert_eq!(1, vec.take_front(1).size());
+        assert_eq!(1, vec.size());
+        vec.trim_front();
+        assert_eq!(1, vec.size());
+        assert_eq!(create_block("x"), vec.coalesce());
+    }
+
+    #[test]
+    fn content_eq() {
+        let mut a = IoVector::new();
+        a.append(create_block("foo"));
+        a.append(create_block("bar"));
+
+        let mut b = IoVector::new();
+        b.append(create_block("fo"));
+        b.append(create_block("oba"));
+        b.append(create_block("r"));
+
+        assert!(a.content_eq(&b));
+        assert!(b.content_eq(&a));
+        assert_ne!(a, b);
+
+        // Consumed data at the start of the first block is not compared.
+        let mut c = IoVector::from(create_block("xfoobar"));
+        c.drop_front(1);
+        assert!(a.content_eq(&c));
+
+        let d = IoVector::from(create_block("foobaz"));
+        assert!(!a.content_eq(&d));
+        let e = IoVector::from(create_block("fooba"));
+        assert!(!a.content_eq(&e));
+        assert!(IoVector::new().content_eq(&IoVector::new()));
+    }
+
+    #[test]
+    fn coalesce_consumed_first_block() {
+        // This state can't be reached through the public API, since fully
+        // consumed blocks are dropped, but coalesce shouldn't rely on that.
+        let mut vec = IoVector::new();
+        vec.append(create_block("foo"));
+        vec.append(create_block("bar"));
+        vec.begin_offset = 3;
+        assert_eq!(create_block("bar"), vec.coalesce());
+
+        // Set up the chain directly, since `append` checks the invariants.
+        let mut vec = IoVector::new();
+        vec.chain.push_back(Block::default());
+        vec.chain.push_back(create_block("baz"));
+        vec.chain_length = 3;
+        assert_eq!(create_block("baz"), vec.coalesce());
+        assert!(vec.content_eq(&IoVector::from(create_block("baz"))));
+    }
+
+    #[test]
+    fn from_slice() {
+        let vec = IoVector::from_slice(b"foobar");
+        assert_eq!(1, vec.block_count());
+        assert_eq!(create_block("foobar"), vec.coalesce());
+
+        let empty = IoVector::from_slice(b"");
+        assert_eq!(0, empty.block_count());
+        assert!(empty.coalesce().is_empty());
+    }
+
+    #[test]
+    fn from_vec() {
+        let data = b"foobar".to_vec();
+        let ptr = data.as_ptr();
+        let vec = IoVector::from_vec(data);
+        assert_eq!(1, vec.block_count());
+        assert_eq!(ptr, vec.front_data().as_ptr());
+        assert_eq!(create_block("foobar"), vec.coalesce());
+    }
+
+    #[test]
+    fn length_prefixed_round_trip() {
+        let mut iov = IoVector::new();
+        iov.append(create_block("abc"));
+        iov.append(create_block("defgh"));
+        iov.drop_front(1);
+
+        let mut cursor = io::Cursor::new(Vec::new());
+        iov.write_length_prefixed(&mut cursor).unwrap();
+        iov.write_length_prefixed(&mut cursor).unwrap();
+        assert_eq!(b"\x07\0\0\0bcdefgh", &cursor.get_ref()[..11]);
+
+        cursor.set_position(0);
+        let read = IoVector::read_length_prefixed(&mut cursor).unwrap();
+        assert!(read.content_eq(&iov));
+        assert_eq!(1, read.block_count());
+        let read = IoVector::read_length_prefixed(&mut cursor).unwrap();
+        assert!(read.content_eq(&iov));
+
+        let mut truncated = io::Cursor::new(b"\x07\0\0\0bcd".to_vec());
+        let err = IoVector::read_length_prefixed(&mut truncated).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+
+        let mut empty = io::Cursor::new(Vec::new());
+        IoVector::new().write_length_prefixed(&mut empty).unwrap();
+        empty.set_position(0);
+        assert!(IoVector::read_length_prefixed(&mut empty)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn invariants_hold() {
+        let mut iov = IoVector::new();
+        iov.assert_invariants();
+        for s in ["abc", "defgh", "i", "jklmnop"] {
+            iov.append(create_block(s));
+        }
+        iov.append(Block::new(0));
+        iov.drop_front(1);
+        let mut front = iov.take_front(4);
+        front.assert_invariants();
+        let front_tail = front.take_front(3);
+        front_tail.assert_invariants();
+        iov.append_iovector(front);
+        iov.trim_front();
+        iov.consume(|data| data.len().min(2));
+        iov.try_drop_front(1).unwrap();
+        let rest = iov.try_take_front(iov.size()).unwrap();
+        rest.assert_invariants();
+        iov.assert_invariants();
+        iov.clear();
+        iov.assert_invariants();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "chain_length out of sync")]
+    fn invariants_catch_bad_length() {
+        let mut iov = IoVector::from(create_block("abc"));
+        iov.chain_length += 1;
+        iov.assert_invariants();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "past the first block")]
+    fn invariants_catch_bad_offset() {
+        let mut iov = IoVector::from(create_block("abc"));
+        iov.begin_offset = 3;
+        iov.append(create_block("def"));
+    }
+
+    #[test]
+    fn defragment() {
+        let mut iov = IoVector::new();
+        for i in 0..100u8 {
+            iov.append(Block::from(vec![i; 3]));
+        }
+        iov.append(Block::from(vec![0xff; 64]));
+        iov.append(create_block("tail"));
+        iov.drop_front(2);
+        let before = iov.coalesce();
+        let size = iov.size();
+
+        iov.defragment(32);
+        // 300 bytes in runs of 33, the big block, and the tail on its own.
+        assert_eq!(10 + 1 + 1, iov.block_count());
+        assert_eq!(size, iov.size());
+        assert_eq!(before, iov.coalesce());
+        assert_eq!(2, iov.begin_offset);
+        assert_eq!(&[0, 1, 1, 1], &iov.front_data()[..4]);
+        assert!(iov.iter_slices().any(|data| data == [0xff; 64]));
+
+        // Defragmenting again has nothing left to merge.
+        iov.defragment(32);
+        assert_eq!(12, iov.block_count());
+        assert_eq!(before, iov.coalesce());
+
+        let mut empty = IoVector::new();
+        empty.defragment(32);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn into_apacket() {
+        use crate::packet::{calculate_checksum, A_VERSION_MIN, A_WRTE};
+
+        let mut iov = IoVector::new();
+        iov.append(create_block("foo"));
+        iov.append(create_block("bar"));
+        iov.append(create_block("baz"));
+        let packet = iov.into_apacket(A_WRTE, 1, 2);
+        assert!(packet.is_command(A_WRTE));
+        assert_eq!((1, 2), (packet.msg.arg0, packet.msg.arg1));
+        assert_eq!(create_block("foobarbaz"), packet.payload);
+        assert_eq!(9, packet.msg.data_length);
+        assert_eq!(calculate_checksum(b"foobarbaz"), packet.msg.data_check);
+        assert!(packet.validate(A_VERSION_MIN).is_ok());
+
+        // A single block is moved into the packet as is.
+        let block = create_block("hello");
+        let data = block.as_ptr();
+        let packet = IoVector::from(block).into_apacket(A_WRTE, 1, 2);
+        assert_eq!(data, packet.payload.as_ptr());
+        assert!(packet.validate(A_VERSION_MIN).is_ok());
+    }
+}
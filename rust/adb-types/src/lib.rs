@@ -0,0 +1,15 @@
+//! A Rust port of the core adb data types.
+//!
+//! This crate is a port of the C++ implementation in `original/types.h`.
+
+pub mod block;
+pub mod decoder;
+pub mod iovector;
+pub mod packet;
+pub mod window;
+
+pub use block::{Block, BlockCursor, BlockError};
+pub use decoder::{DecodeError, FrameDecoder};
+pub use iovector::{IoVector, IoVectorError};
+pub use packet::{Amessage, AmessageBuilder, AmessageError, Apacket, PacketError, ParseError};
+pub use window::SlidingWindow;
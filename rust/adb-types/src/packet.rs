@@ -0,0 +1,777 @@
+use crate::Block;
+use std::io::{self, Write};
+use thiserror::Error;
+
+pub const A_SYNC: u32 = 0x434e5953;
+pub const A_CNXN: u32 = 0x4e584e43;
+pub const A_OPEN: u32 = 0x4e45504f;
+pub const A_OKAY: u32 = 0x59414b4f;
+pub const A_CLSE: u32 = 0x45534c43;
+pub const A_WRTE: u32 = 0x45545257;
+pub const A_AUTH: u32 = 0x48545541;
+pub const A_STLS: u32 = 0x534c5453;
+
+// ADB protocol version.
+// Version revision:
+// 0x01000000: original
+// 0x01000001: skip checksum (Dec 2017)
+pub const A_VERSION_MIN: u32 = 0x01000000;
+pub const A_VERSION_SKIP_CHECKSUM: u32 = 0x01000001;
+pub const A_VERSION: u32 = 0x01000001;
+
+/// The version of the TLS handshake requested by `A_STLS`.
+pub const A_STLS_VERSION: u32 = 0x01000000;
+
+/// The maximum payload size of the original protocol version.
+pub const MAX_PAYLOAD_V1: usize = 4 * 1024;
+/// The maximum payload size.
+pub const MAX_PAYLOAD: usize = 1024 * 1024;
+
+/// Error type for validating an `Amessage`.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AmessageError {
+    /// The command is not one of the known `A_*` commands.
+    #[error("Unknown command {0:#010x}")]
+    UnknownCommand(u32),
+    /// The protocol version is older than `A_VERSION_MIN`.
+    #[error("Unsupported protocol version {0:#010x}")]
+    UnsupportedVersion(u32),
+    /// The command is not available in the negotiated protocol version.
+    #[error("Command {command:#010x} is not available in protocol version {version:#010x}")]
+    CommandNotInVersion { command: u32, version: u32 },
+}
+
+/// Error type for parsing an `Apacket` from bytes, or validating one.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseError {
+    /// The buffer is shorter than the header, or than the header and the
+    /// payload length it declares.
+    #[error("Expected {expected} bytes, got only {actual}")]
+    Truncated { expected: usize, actual: usize },
+    /// The buffer is longer than the header and the payload length it
+    /// declares.
+    #[error("Expected {expected} bytes, got {actual}")]
+    TrailingData { expected: usize, actual: usize },
+    /// The magic is not the inverse of the command.
+    #[error("Invalid magic {magic:#010x} for command {command:#010x}")]
+    BadMagic { command: u32, magic: u32 },
+    /// The length of the payload doesn't match `data_length`.
+    #[error("Payload of {actual} bytes doesn't match data_length {expected}")]
+    LengthMismatch { expected: u32, actual: usize },
+    /// The checksum of the payload doesn't match `data_check`.
+    #[error("Checksum {actual:#010x} doesn't match data_check {expected:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+/// The error returned by `Apacket::validate`, which is the same as for
+/// parsing a packet, since parsing validates it too.
+pub type PacketError = ParseError;
+
+/// The header of an adb packet.
+///
+/// This is a port of the C++ `amessage` in `original/types.h`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Amessage {
+    /// Command identifier constant.
+    pub command: u32,
+    /// First argument.
+    pub arg0: u32,
+    /// Second argument.
+    pub arg1: u32,
+    /// Length of payload (0 is allowed).
+    pub data_length: u32,
+    /// Checksum of data payload.
+    pub data_check: u32,
+    /// `command ^ 0xffffffff`.
+    pub magic: u32,
+}
+
+impl Amessage {
+    /// The size of the header on the wire.
+    pub const HEADER_SIZE: usize = 24;
+
+    /// Checks that the command of this message is legal in the negotiated
+    /// protocol `version`.
+    ///
+    /// `A_STLS` was introduced after the original protocol version, and is
+    /// rejected for connections negotiated at an older version.
+    pub fn validate_for_version(&self, version: u32) -> Result<(), AmessageError> {
+        if version < A_VERSION_MIN {
+            return Err(AmessageError::UnsupportedVersion(version));
+        }
+        let min_version = match self.command {
+            A_SYNC | A_CNXN | A_OPEN | A_OKAY | A_CLSE | A_WRTE | A_AUTH => A_VERSION_MIN,
+            A_STLS => A_VERSION_SKIP_CHECKSUM,
+            command => return Err(AmessageError::UnknownCommand(command)),
+        };
+        if version < min_version {
+            return Err(AmessageError::CommandNotInVersion {
+                command: self.command,
+                version,
+            });
+        }
+        Ok(())
+    }
+
+    /// Serializes the header as it's sent on the wire: the six fields in
+    /// little-endian order, in the layout of the C++ `amessage`.
+    pub fn to_bytes(&self) -> [u8; Self::HEADER_SIZE] {
+        let mut buf = [0u8; Self::HEADER_SIZE];
+        let fields = [
+            self.command,
+            self.arg0,
+            self.arg1,
+            self.data_length,
+            self.data_check,
+            self.magic,
+        ];
+        for (chunk, field) in buf.chunks_exact_mut(4).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Parses a header serialized by `to_bytes` from the first `HEADER_SIZE`
+    /// bytes of `buf`. Any bytes after the header are ignored.
+    ///
+    /// Unlike `Apacket::try_from`, this doesn't validate the fields.
+    pub fn from_bytes(buf: &[u8]) -> Result<Amessage, ParseError> {
+        if buf.len() < Self::HEADER_SIZE {
+            return Err(ParseError::Truncated {
+                expected: Self::HEADER_SIZE,
+                actual: buf.len(),
+            });
+        }
+        Ok(Self::read_from(buf))
+    }
+
+    /// Reads the header from the first `HEADER_SIZE` bytes of `buf`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is shorter than `HEADER_SIZE`.
+    pub(crate) fn read_from(buf: &[u8]) -> Amessage {
+        let field = |i: usize| u32::from_le_bytes(buf[i * 4..i * 4 + 4].try_into().unwrap());
+        Amessage {
+            command: field(0),
+            arg0: field(1),
+            arg1: field(2),
+            data_length: field(3),
+            data_check: field(4),
+            magic: field(5),
+        }
+    }
+
+    /// Returns `(arg0, arg1)`, which for the stream commands (`A_OPEN`,
+    /// `A_OKAY`, `A_WRTE` and `A_CLSE`) are `(local_id, remote_id)` from the
+    /// point of view of the sender of the message.
+    ///
+    /// The receiver should route the message to its stream with id
+    /// `remote_id`. For other commands, the arguments have other meanings.
+    pub fn stream_ids(&self) -> (u32, u32) {
+        (self.arg0, self.arg1)
+    }
+}
+
+/// An adb packet, consisting of a header and its payload.
+///
+/// This is a port of the C++ `apacket` in `original/types.h`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Apacket {
+    pub msg: Amessage,
+    pub payload: Block,
+}
+
+impl Apacket {
+    /// Creates an `A_CNXN` packet, announcing the protocol `version` and the
+    /// largest payload the sender accepts, with the connection `banner` as
+    /// the payload.
+    ///
+    /// This is the packet built by `send_connect` in `original/adb.cpp`.
+    pub fn connect(version: u32, max_payload: u32, banner: &[u8]) -> Apacket {
+        Self::build(A_CNXN, version, max_payload, banner)
+    }
+
+    /// Creates an `A_AUTH` packet of the given `auth_type` (token, signature
+    /// or public key).
+    pub fn auth(auth_type: u32, payload: &[u8]) -> Apacket {
+        Self::build(A_AUTH, auth_type, 0, payload)
+    }
+
+    /// Creates an `A_OPEN` packet, opening a stream with id `local_id` to the
+    /// service `destination`.
+    ///
+    /// Like `connect_to_remote` in `original/sockets.cpp`, the destination is
+    /// sent with a trailing NUL, which older versions of adbd expect.
+    pub fn open(local_id: u32, destination: &str) -> Apacket {
+        let mut payload = Vec::with_capacity(destination.len() + 1);
+        payload.extend_from_slice(destination.as_bytes());
+        payload.push(0);
+        Self::build(A_OPEN, local_id, 0, &payload)
+    }
+
+    /// Creates an `A_OKAY` packet, acknowledging stream `remote_id` from the
+    /// sender's stream `local_id`.
+    pub fn okay(local_id: u32, remote_id: u32) -> Apacket {
+        Self::build(A_OKAY, local_id, remote_id, &[])
+    }
+
+    /// Creates an `A_WRTE` packet, sending `payload` to stream `remote_id`
+    /// from the sender's stream `local_id`.
+    pub fn write(local_id: u32, remote_id: u32, payload: &[u8]) -> Apacket {
+        Self::build(A_WRTE, local_id, remote_id, payload)
+    }
+
+    /// Creates an `A_CLSE` packet, closing stream `remote_id` from the
+    /// sender's stream `local_id`.
+    pub fn close(local_id: u32, remote_id: u32) -> Apacket {
+        Self::build(A_CLSE, local_id, remote_id, &[])
+    }
+
+    /// Creates an `A_STLS` packet, requesting to switch the connection to
+    /// TLS.
+    pub fn stls() -> Apacket {
+        Self::build(A_STLS, A_STLS_VERSION, 0, &[])
+    }
+
+    fn build(command: u32, arg0: u32, arg1: u32, payload: &[u8]) -> Apacket {
+        AmessageBuilder::new()
+            .command(command)
+            .arg0(arg0)
+            .arg1(arg1)
+            .payload(Block::from_slice(payload))
+            .build()
+    }
+
+    /// Returns whether this packet's command is `command`, one of the `A_*`
+    /// constants.
+    pub fn is_command(&self, command: u32) -> bool {
+        self.msg.command == command
+    }
+
+    /// Returns the number of bytes this packet occupies on the wire, i.e. the
+    /// header plus the payload.
+    ///
+    /// This is the single source of truth for flow-control accounting.
+    pub fn wire_size(&self) -> usize {
+        Amessage::HEADER_SIZE + self.payload.len()
+    }
+
+    /// Moves the payload out of the packet without copying it, leaving an
+    /// empty payload behind, e.g. to hand a `A_WRTE` payload to its stream.
+    ///
+    /// The header is left as is, so the packet must be resealed before it is
+    /// sent again.
+    pub fn take_payload(&mut self) -> Block {
+        std::mem::take(&mut self.payload)
+    }
+
+    /// Returns the checksum of the payload, as used by the legacy adb
+    /// protocol.
+    pub fn compute_checksum(&self) -> u32 {
+        calculate_checksum(&self.payload)
+    }
+
+    /// Fills in the `data_length`, `data_check` and `magic` fields of the
+    /// header from the command and the payload.
+    pub fn seal(&mut self) {
+        self.msg.data_length = self.payload.len() as u32;
+        self.msg.data_check = self.compute_checksum();
+        self.msg.magic = self.msg.command ^ 0xffffffff;
+    }
+
+    /// Checks the `data_length`, `data_check` and `magic` fields of the
+    /// header against the command and the payload, e.g. to reject a
+    /// corrupted packet before acting on it.
+    ///
+    /// Like `check_data` in `original/transport.cpp`, the checksum is only
+    /// checked for a negotiated protocol `version` older than
+    /// `A_VERSION_SKIP_CHECKSUM`, since newer peers send a `data_check` of 0.
+    pub fn validate(&self, version: u32) -> Result<(), PacketError> {
+        self.validate_with(version < A_VERSION_SKIP_CHECKSUM)
+    }
+
+    fn validate_with(&self, check_checksum: bool) -> Result<(), PacketError> {
+        let msg = &self.msg;
+        if msg.magic != msg.command ^ 0xffffffff {
+            return Err(ParseError::BadMagic {
+                command: msg.command,
+                magic: msg.magic,
+            });
+        }
+        if msg.data_length as usize != self.payload.len() {
+            return Err(ParseError::LengthMismatch {
+                expected: msg.data_length,
+                actual: self.payload.len(),
+            });
+        }
+        if check_checksum {
+            let checksum = self.compute_checksum();
+            if checksum != msg.data_check {
+                return Err(ParseError::ChecksumMismatch {
+                    expected: msg.data_check,
+                    actual: checksum,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the packet to `writer`: the header fields in little-endian
+    /// order, followed by the payload.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.msg.to_bytes())?;
+        writer.write_all(&self.payload)
+    }
+}
+
+/// Parses a complete packet, which must be exactly the header followed by
+/// `data_length` bytes of payload.
+///
+/// Like `check_header` in `original/transport.cpp`, the magic must be the
+/// inverse of the command. Since peers at `A_VERSION_SKIP_CHECKSUM` and later
+/// send a `data_check` of 0, the checksum is only validated when it's not 0.
+impl TryFrom<&[u8]> for Apacket {
+    type Error = ParseError;
+
+    fn try_from(buf: &[u8]) -> Result<Self, ParseError> {
+        let msg = Amessage::from_bytes(buf)?;
+        if msg.magic != msg.command ^ 0xffffffff {
+            return Err(ParseError::BadMagic {
+                command: msg.command,
+                magic: msg.magic,
+            });
+        }
+
+        let expected = Amessage::HEADER_SIZE + msg.data_length as usize;
+        if buf.len() < expected {
+            return Err(ParseError::Truncated {
+                expected,
+                actual: buf.len(),
+            });
+        }
+        if buf.len() > expected {
+            return Err(ParseError::TrailingData {
+                expected,
+                actual: buf.len(),
+            });
+        }
+
+        let packet = Apacket {
+            msg,
+            payload: Block::from_slice(&buf[Amessage::HEADER_SIZE..]),
+        };
+        // The protocol version isn't known here.
+        packet.validate_with(packet.msg.data_check != 0)?;
+        Ok(packet)
+    }
+}
+
+/// Calculates the checksum of a payload, as used by the legacy adb protocol.
+///
+/// This is a port of `calculate_apacket_checksum` in `original/adb.cpp`.
+pub fn calculate_checksum(payload: &[u8]) -> u32 {
+    payload
+        .iter()
+        .fold(0u32, |sum, &b| sum.wrapping_add(u32::from(b)))
+}
+
+/// A builder for an `Apacket`, setting the header fields by name.
+///
+/// The `data_length`, `data_check` and `magic` fields are derived from the
+/// command and the payload when the packet is built.
+///
+/// ```
+/// use adb_types::{AmessageBuilder, Block};
+///
+/// let packet = AmessageBuilder::new()
+///     .command(0x45545257)
+///     .arg0(1)
+///     .arg1(2)
+///     .payload(Block::from_slice(b"hello"))
+///     .build();
+/// assert_eq!(5, packet.msg.data_length);
+/// ```
+#[derive(Debug, Default)]
+pub struct AmessageBuilder {
+    command: u32,
+    arg0: u32,
+    arg1: u32,
+    payload: Block,
+}
+
+impl AmessageBuilder {
+    /// Creates a new builder with all fields zeroed and an empty payload.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the command identifier.
+    pub fn command(mut self, command: u32) -> Self {
+        self.command = command;
+        self
+    }
+
+    /// Sets the first argument.
+    pub fn arg0(mut self, arg0: u32) -> Self {
+        self.arg0 = arg0;
+        self
+    }
+
+    /// Sets the second argument.
+    pub fn arg1(mut self, arg1: u32) -> Self {
+        self.arg1 = arg1;
+        self
+    }
+
+    /// Sets the payload of the packet.
+    pub fn payload(mut self, payload: Block) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Builds the packet, computing `data_length`, `data_check` and `magic`.
+    pub fn build(self) -> Apacket {
+        Apacket {
+            msg: Amessage {
+                command: self.command,
+                arg0: self.arg0,
+                arg1: self.arg1,
+                data_length: self.payload.len() as u32,
+                data_check: calculate_checksum(&self.payload),
+                magic: self.command ^ 0xffffffff,
+            },
+            payload: self.payload,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum() {
+        assert_eq!(0, calculate_checksum(&[]));
+        assert_eq!(0x1fe, calculate_checksum(&[0xff, 0xff]));
+    }
+
+    #[test]
+    fn builder() {
+        let payload = Block::from_slice(b"hello");
+        let packet = AmessageBuilder::new()
+            .command(A_WRTE)
+            .arg0(1)
+            .arg1(2)
+            .payload(payload.clone())
+            .build();
+
+        let expected = Apacket {
+            msg: Amessage {
+                command: A_WRTE,
+                arg0: 1,
+                arg1: 2,
+                data_length: 5,
+                data_check: calculate_checksum(b"hello"),
+                magic: !A_WRTE,
+            },
+            payload,
+        };
+        assert_eq!(expected, packet);
+    }
+
+    #[test]
+    fn builder_empty_payload() {
+        let packet = AmessageBuilder::new().command(A_WRTE).build();
+        assert_eq!(0, packet.msg.data_length);
+        assert_eq!(0, packet.msg.data_check);
+        assert_eq!(!A_WRTE, packet.msg.magic);
+        assert!(packet.payload.is_empty());
+    }
+
+    #[test]
+    fn validate_for_version() {
+        let stls = AmessageBuilder::new().command(A_STLS).build().msg;
+        assert_eq!(
+            Err(AmessageError::CommandNotInVersion {
+                command: A_STLS,
+                version: A_VERSION_MIN
+            }),
+            stls.validate_for_version(A_VERSION_MIN)
+        );
+        assert_eq!(Ok(()), stls.validate_for_version(A_VERSION));
+
+        let cnxn = AmessageBuilder::new().command(A_CNXN).build().msg;
+        assert_eq!(Ok(()), cnxn.validate_for_version(A_VERSION_MIN));
+        assert_eq!(
+            Err(AmessageError::UnsupportedVersion(0x00ffffff)),
+            cnxn.validate_for_version(0x00ffffff)
+        );
+
+        let unknown = AmessageBuilder::new().command(0x12345678).build().msg;
+        assert_eq!(
+            Err(AmessageError::UnknownCommand(0x12345678)),
+            unknown.validate_for_version(A_VERSION)
+        );
+    }
+
+    #[test]
+    fn wire_size() {
+        let packet = AmessageBuilder::new()
+            .command(A_WRTE)
+            .arg0(1)
+            .arg1(2)
+            .payload(Block::from_slice(b"hello"))
+            .build();
+        let mut buf = Vec::new();
+        packet.write_to(&mut buf).unwrap();
+        assert_eq!(Amessage::HEADER_SIZE + 5, packet.wire_size());
+        assert_eq!(buf.len(), packet.wire_size());
+        assert_eq!(&A_WRTE.to_le_bytes(), &buf[..4]);
+        assert_eq!(b"hello", &buf[Amessage::HEADER_SIZE..]);
+
+        let empty = AmessageBuilder::new().command(A_OKAY).build();
+        let mut buf = Vec::new();
+        empty.write_to(&mut buf).unwrap();
+        assert_eq!(Amessage::HEADER_SIZE, empty.wire_size());
+        assert_eq!(buf.len(), empty.wire_size());
+    }
+
+    #[test]
+    fn take_payload() {
+        let mut packet = Apacket::write(1, 2, b"hello");
+        let payload = packet.take_payload();
+        assert_eq!(b"hello", payload.as_slice());
+        assert!(packet.payload.is_empty());
+        assert_eq!(5, packet.msg.data_length);
+        assert!(packet.validate(A_VERSION_MIN).is_err());
+
+        packet.seal();
+        assert_eq!(0, packet.msg.data_length);
+        assert!(packet.validate(A_VERSION_MIN).is_ok());
+    }
+
+    #[test]
+    fn stream_ids() {
+        let packet = AmessageBuilder::new()
+            .command(A_OKAY)
+            .arg0(3)
+            .arg1(7)
+            .build();
+        assert!(packet.is_command(A_OKAY));
+        assert!(!packet.is_command(A_WRTE));
+        assert_eq!((3, 7), packet.msg.stream_ids());
+
+        // The reply swaps the ids, since the sender's remote stream is the
+        // receiver's local one.
+        let (local_id, remote_id) = packet.msg.stream_ids();
+        let reply = AmessageBuilder::new()
+            .command(A_OKAY)
+            .arg0(remote_id)
+            .arg1(local_id)
+            .build();
+        assert_eq!((7, 3), reply.msg.stream_ids());
+    }
+
+    #[test]
+    fn amessage_bytes_round_trip() {
+        let msg = Amessage {
+            command: A_WRTE,
+            arg0: 1,
+            arg1: 0x12345678,
+            data_length: 5,
+            data_check: 0x1fe,
+            magic: !A_WRTE,
+        };
+        let bytes = msg.to_bytes();
+        assert_eq!(&A_WRTE.to_le_bytes(), &bytes[..4]);
+        assert_eq!(&[0x78, 0x56, 0x34, 0x12], &bytes[8..12]);
+        assert_eq!(Ok(msg), Amessage::from_bytes(&bytes));
+
+        assert_eq!(
+            Err(ParseError::Truncated {
+                expected: 24,
+                actual: 23
+            }),
+            Amessage::from_bytes(&bytes[..23])
+        );
+    }
+
+    #[test]
+    fn constructors() {
+        let check = |packet: &Apacket, command, arg0, arg1, payload: &[u8]| {
+            assert_eq!(Ok(()), packet.validate(A_VERSION_MIN));
+            assert!(packet.is_command(command));
+            assert_eq!((arg0, arg1), (packet.msg.arg0, packet.msg.arg1));
+            assert_eq!(payload, packet.payload.as_slice());
+            assert_eq!(payload.len() as u32, packet.msg.data_length);
+        };
+
+        let banner = b"host::features=shell_v2";
+        check(
+            &Apacket::connect(A_VERSION, MAX_PAYLOAD as u32, banner),
+            A_CNXN,
+            A_VERSION,
+            MAX_PAYLOAD as u32,
+            banner,
+        );
+        check(&Apacket::auth(1, b"token"), A_AUTH, 1, 0, b"token");
+        check(&Apacket::open(3, "shell:ls"), A_OPEN, 3, 0, b"shell:ls\0");
+        check(&Apacket::okay(3, 7), A_OKAY, 3, 7, b"");
+        check(&Apacket::write(3, 7, b"hello"), A_WRTE, 3, 7, b"hello");
+        check(&Apacket::close(3, 7), A_CLSE, 3, 7, b"");
+        check(&Apacket::stls(), A_STLS, A_STLS_VERSION, 0, b"");
+    }
+
+    #[test]
+    fn seal_and_validate() {
+        let mut packet = Apacket {
+            msg: Amessage {
+                command: A_WRTE,
+                ..Default::default()
+            },
+            payload: Block::from_slice(b"hello"),
+        };
+        assert!(packet.validate(A_VERSION_MIN).is_err());
+        packet.seal();
+        assert_eq!(
+            AmessageBuilder::new()
+                .command(A_WRTE)
+                .payload(Block::from_slice(b"hello"))
+                .build(),
+            packet
+        );
+        assert_eq!(calculate_checksum(b"hello"), packet.compute_checksum());
+        assert_eq!(Ok(()), packet.validate(A_VERSION_MIN));
+
+        let mut skip_checksum = packet.clone();
+        skip_checksum.msg.data_check = 0;
+        assert_eq!(Ok(()), skip_checksum.validate(A_VERSION_SKIP_CHECKSUM));
+        assert!(skip_checksum.validate(A_VERSION_MIN).is_err());
+    }
+
+    #[test]
+    fn validate_invalid() {
+        let packet = AmessageBuilder::new()
+            .command(A_WRTE)
+            .payload(Block::from_slice(b"hello"))
+            .build();
+
+        let mut bad_magic = packet.clone();
+        bad_magic.msg.magic = A_WRTE;
+        assert_eq!(
+            Err(ParseError::BadMagic {
+                command: A_WRTE,
+                magic: A_WRTE
+            }),
+            bad_magic.validate(A_VERSION_MIN)
+        );
+
+        let mut bad_checksum = packet.clone();
+        bad_checksum.msg.data_check += 1;
+        assert_eq!(
+            Err(ParseError::ChecksumMismatch {
+                expected: packet.msg.data_check + 1,
+                actual: packet.msg.data_check
+            }),
+            bad_checksum.validate(A_VERSION_MIN)
+        );
+        // Newer peers don't send a checksum, so it isn't checked.
+        assert_eq!(Ok(()), bad_checksum.validate(A_VERSION));
+
+        let mut bad_length = packet;
+        bad_length.payload = Block::from_slice(b"hell");
+        assert_eq!(
+            Err(ParseError::LengthMismatch {
+                expected: 5,
+                actual: 4
+            }),
+            bad_length.validate(A_VERSION_MIN)
+        );
+    }
+
+    fn to_bytes(packet: &Apacket) -> Vec<u8> {
+        let mut buf = Vec::new();
+        packet.write_to(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn try_from_bytes() {
+        let packet = AmessageBuilder::new()
+            .command(A_WRTE)
+            .arg0(1)
+            .arg1(2)
+            .payload(Block::from_slice(b"hello"))
+            .build();
+        let bytes = to_bytes(&packet);
+        assert_eq!(Ok(packet.clone()), Apacket::try_from(bytes.as_slice()));
+
+        // A data_check of 0 is sent by peers which skip the checksum.
+        let mut skip_checksum = packet.clone();
+        skip_checksum.msg.data_check = 0;
+        let bytes = to_bytes(&skip_checksum);
+        assert_eq!(Ok(skip_checksum), Apacket::try_from(bytes.as_slice()));
+    }
+
+    #[test]
+    fn try_from_bytes_wrong_length() {
+        let packet = AmessageBuilder::new()
+            .command(A_WRTE)
+            .payload(Block::from_slice(b"hello"))
+            .build();
+        let bytes = to_bytes(&packet);
+        assert_eq!(
+            Err(ParseError::Truncated {
+                expected: 24,
+                actual: 10
+            }),
+            Apacket::try_from(&bytes[..10])
+        );
+        assert_eq!(
+            Err(ParseError::Truncated {
+                expected: 29,
+                actual: 28
+            }),
+            Apacket::try_from(&bytes[..28])
+        );
+
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(
+            Err(ParseError::TrailingData {
+                expected: 29,
+                actual: 30
+            }),
+            Apacket::try_from(long.as_slice())
+        );
+    }
+
+    #[test]
+    fn try_from_bytes_invalid() {
+        let packet = AmessageBuilder::new()
+            .command(A_WRTE)
+            .payload(Block::from_slice(b"hello"))
+            .build();
+
+        let mut bad_checksum = packet.clone();
+        bad_checksum.msg.data_check += 1;
+        assert_eq!(
+            Err(ParseError::ChecksumMismatch {
+                expected: packet.msg.data_check + 1,
+                actual: packet.msg.data_check
+            }),
+            Apacket::try_from(to_bytes(&bad_checksum).as_slice())
+        );
+
+        let mut bad_magic = packet;
+        bad_magic.msg.magic = A_WRTE;
+        assert_eq!(
+            Err(ParseError::BadMagic {
+                command: A_WRTE,
+                magic: A_WRTE
+            }),
+            Apacket::try_from(to_bytes(&bad_magic).as_slice())
+        );
+    }
+}
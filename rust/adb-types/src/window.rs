@@ -0,0 +1,112 @@
+/// The number of bytes a stream may send before its first ack, when delayed
+/// acks are supported.
+///
+/// This is `INITIAL_DELAYED_ACK_BYTES` in `original/adb.h`.
+pub const INITIAL_DELAYED_ACK_BYTES: usize = 32 * 1024 * 1024;
+
+/// Tracks the bytes of a stream which have been sent but not yet acked, to
+/// decide when an `A_WRTE` may be sent.
+///
+/// With delayed acks, the receiver of a stream reports the number of bytes it
+/// has consumed in its `A_OKAY`s, and the sender stops writing while
+/// `max_payload` bytes are unacknowledged. This is the accounting of
+/// `available_send_bytes` in `original/sockets.cpp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlidingWindow {
+    bytes_sent: u64,
+    bytes_acked: u64,
+    max_payload: usize,
+}
+
+impl SlidingWindow {
+    /// Creates a window allowing up to `max_payload` unacknowledged bytes.
+    pub fn new(max_payload: usize) -> Self {
+        Self {
+            bytes_sent: 0,
+            bytes_acked: 0,
+            max_payload,
+        }
+    }
+
+    /// Returns the total number of bytes sent.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns the total number of bytes acked by the receiver.
+    pub fn bytes_acked(&self) -> u64 {
+        self.bytes_acked
+    }
+
+    /// Returns the maximum number of unacknowledged bytes.
+    pub fn max_payload(&self) -> usize {
+        self.max_payload
+    }
+
+    /// Returns the number of bytes sent but not yet acked.
+    pub fn in_flight(&self) -> u64 {
+        self.bytes_sent - self.bytes_acked
+    }
+
+    /// Returns whether `len` more bytes can be sent without exceeding the
+    /// window.
+    pub fn can_send(&self, len: usize) -> bool {
+        self.in_flight() + len as u64 <= self.max_payload as u64
+    }
+
+    /// Records that `len` bytes were sent.
+    pub fn on_send(&mut self, len: usize) {
+        self.bytes_sent += len as u64;
+    }
+
+    /// Records that the receiver acked `bytes` more bytes.
+    ///
+    /// An ack for more bytes than are in flight only acks those in flight, so
+    /// that a misbehaving peer can't grow the window beyond `max_payload`.
+    pub fn on_ack(&mut self, bytes: usize) {
+        self.bytes_acked += (bytes as u64).min(self.in_flight());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stall_and_resume() {
+        let mut window = SlidingWindow::new(10);
+        assert!(window.can_send(10));
+        assert!(!window.can_send(11));
+
+        window.on_send(4);
+        window.on_send(4);
+        assert_eq!(8, window.in_flight());
+        assert!(window.can_send(2));
+        assert!(!window.can_send(4));
+
+        window.on_send(2);
+        assert!(!window.can_send(1));
+        assert!(window.can_send(0));
+
+        // The receiver consumed the first write.
+        window.on_ack(4);
+        assert_eq!(6, window.in_flight());
+        assert!(window.can_send(4));
+        assert!(!window.can_send(5));
+
+        window.on_ack(6);
+        assert_eq!(0, window.in_flight());
+        assert_eq!(10, window.bytes_sent());
+        assert_eq!(10, window.bytes_acked());
+        assert!(window.can_send(10));
+    }
+
+    #[test]
+    fn over_ack() {
+        let mut window = SlidingWindow::new(10);
+        window.on_send(4);
+        window.on_ack(100);
+        assert_eq!(0, window.in_flight());
+        assert!(!window.can_send(11));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::protocol::{parse_protocol_length, MAX_PROTOCOL_STRING_LENGTH};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Writes a protocol-format string like `send_protocol_string`, without
+/// blocking the async task.
+///
+/// Fails with `InvalidInput` if `s` is longer than
+/// `MAX_PROTOCOL_STRING_LENGTH`.
+pub async fn send_protocol_string_async<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    s: &str,
+) -> io::Result<()> {
+    if s.len() > MAX_PROTOCOL_STRING_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "protocol string too long",
+        ));
+    }
+    writer
+        .write_all(format!("{:04x}{}", s.len(), s).as_bytes())
+        .await
+}
+
+/// Reads a protocol-format string like `read_protocol_string`, without
+/// blocking the async task.
+///
+/// Fails with `InvalidData` if the length is not valid hex or the data is not
+/// valid UTF-8, and with `UnexpectedEof` if the stream ends early.
+pub async fn read_protocol_string_async<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> io::Result<String> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len).await?;
+    let len = parse_protocol_length(&len)?;
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf).await?;
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn round_trip() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        send_protocol_string_async(&mut client, "host:version")
+            .await
+            .unwrap();
+        assert_eq!(
+            "host:version",
+            read_protocol_string_async(&mut server).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_too_long() {
+        let (mut client, _server) = tokio::io::duplex(64);
+        let s = "x".repeat(MAX_PROTOCOL_STRING_LENGTH + 1);
+        let err = send_protocol_string_async(&mut client, &s)
+            .await
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[tokio::test]
+    async fn read_invalid_length() {
+        let err = read_protocol_string_async(&mut &b"00g1x"[..])
+            .await
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[tokio::test]
+    async fn read_short() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client.write_all(b"0005abc").await.unwrap();
+        drop(client);
+        let err = read_protocol_string_async(&mut server).await.unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::protocol::parse_protocol_length;
+use std::io::{self, Read};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Instant;
+
+/// Reads a protocol-format string like `read_protocol_string`, failing with
+/// `TimedOut` unless the whole string, including its length, arrives before
+/// `deadline`.
+///
+/// This bounds the wait for the whole message rather than for each read, so
+/// a peer trickling in one byte at a time can't stall the caller. Readiness
+/// is polled on the file descriptor, so `reader` must not buffer data
+/// internally (e.g. it shouldn't be a `BufReader`).
+pub fn read_protocol_string_deadline<R: Read + AsRawFd>(
+    reader: &mut R,
+    deadline: Instant,
+) -> io::Result<String> {
+    let mut len = [0u8; 4];
+    read_exact_deadline(reader, &mut len, deadline)?;
+    let mut buf = vec![0; parse_protocol_length(&len)?];
+    read_exact_deadline(reader, &mut buf, deadline)?;
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_exact_deadline<R: Read + AsRawFd>(
+    reader: &mut R,
+    mut buf: &mut [u8],
+    deadline: Instant,
+) -> io::Result<()> {
+    while !buf.is_empty() {
+        wait_readable(reader.as_raw_fd(), deadline)?;
+        match reader.read(buf) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => buf = &mut buf[n..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Waits until `fd` is readable, or fails with `TimedOut` once `deadline`
+/// has passed.
+fn wait_readable(fd: RawFd, deadline: Instant) -> io::Result<()> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        // Round up, so that we don't wake up just before the deadline.
+        let timeout = remaining.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32;
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: pollfd outlives the call.
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => {}
+            // Errors and hangups are reported by the following read.
+            _ => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn arrives_in_time() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        let writer = thread::spawn(move || {
+            b.write_all(b"000c").unwrap();
+            thread::sleep(Duration::from_millis(10));
+            b.write_all(b"host:version").unwrap();
+        });
+        let deadline = Instant::now() + Duration::from_secs(10);
+        assert_eq!(
+            "host:version",
+            read_protocol_string_deadline(&mut a, deadline).unwrap()
+        );
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn trickle_misses_deadline() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        // Each byte arrives well within the deadline on its own, but the
+        // whole message doesn't.
+        let writer = thread::spawn(move || {
+            for &byte in b"0005hello" {
+                if b.write_all(&[byte]).is_err() {
+                    return;
+                }
+                if done_rx.recv_timeout(Duration::from_millis(50)).is_ok() {
+                    return;
+                }
+            }
+        });
+        let start = Instant::now();
+        let err =
+            read_protocol_string_deadline(&mut a, start + Duration::from_millis(120)).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert!(start.elapsed() >= Duration::from_millis(120));
+        done_tx.send(()).unwrap();
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn eof() {
+        let (mut a, mut b) = UnixStream::pair().unwrap();
+        b.write_all(b"0005he").unwrap();
+        drop(b);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let err = read_protocol_string_deadline(&mut a, deadline).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+}
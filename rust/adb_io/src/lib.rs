@@ -0,0 +1,81 @@
+//! A Rust port of the I/O helpers in `original/adb_io.h`.
+//!
+//! Several of the C++ helpers have direct equivalents in the standard
+//! library, and callers should use those instead:
+//!
+//! * `ReadFdExactly` is `std::io::Read::read_exact`.
+//! * `WriteFdExactly` is `std::io::Write::write_all`.
+//! * `WriteFdFmt` is `write!` on a `std::io::Write`.
+//!
+//! With the `tokio` feature, async versions of the protocol string helpers
+//! are available for callers running on a tokio runtime.
+
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(unix)]
+mod deadline;
+mod protocol;
+#[cfg(unix)]
+mod shutdown;
+mod status;
+mod tee;
+mod vectored;
+
+#[cfg(feature = "tokio")]
+pub use async_io::{read_protocol_string_async, send_protocol_string_async};
+#[cfg(unix)]
+pub use deadline::read_protocol_string_deadline;
+pub use protocol::{
+    read_protocol_string, read_protocol_string_limited, read_protocol_string_streaming,
+    read_protocol_u32, send_protocol_string, send_protocol_u32, ProtocolStringIter,
+    MAX_PROTOCOL_STRING_LENGTH,
+};
+#[cfg(unix)]
+pub use shutdown::shutdown_write;
+pub use status::{read_status, send_fail, send_okay, Status};
+pub use tee::{TeeReader, TeeWriter};
+pub use vectored::write_vectored_all;
+
+/// Formats up to the first 16 bytes of `data` as hex, followed by their
+/// printable ASCII representation.
+///
+/// This is a port of `dump_hex` in `original/adb_utils.cpp`.
+pub fn dump_hex(data: &[u8]) -> String {
+    const TRUNCATE_LEN: usize = 16;
+    let truncated = data.len() > TRUNCATE_LEN;
+    let data = &data[..data.len().min(TRUNCATE_LEN)];
+
+    let mut line: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+    line.push(' ');
+    line.extend(data.iter().map(|&b| {
+        if b.is_ascii_graphic() || b == b' ' {
+            b as char
+        } else {
+            '.'
+        }
+    }));
+
+    if truncated {
+        line.push_str(" [truncated]");
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_hex_short() {
+        assert_eq!("4f4b4159 OKAY", dump_hex(b"OKAY"));
+        assert_eq!("00ff41 ..A", dump_hex(&[0x00, 0xff, 0x41]));
+    }
+
+    #[test]
+    fn dump_hex_truncated() {
+        assert_eq!(
+            "30313233343536373839616263646566 0123456789abcdef [truncated]",
+            dump_hex(b"0123456789abcdefg")
+        );
+    }
+}
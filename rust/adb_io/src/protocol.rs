@@ -0,0 +1,330 @@
+use std::io::{self, Read, Write};
+
+/// The maximum length of a protocol string, since the length is encoded as
+/// four hex digits.
+pub const MAX_PROTOCOL_STRING_LENGTH: usize = 0xffff;
+
+/// The size of the chunks passed to the sink by
+/// `read_protocol_string_streaming`.
+const STREAMING_CHUNK_SIZE: usize = 4096;
+
+/// Writes a protocol-format string; a four hex digit length followed by the
+/// string data.
+///
+/// This is a port of `SendProtocolString` in `original/adb_io.cpp`. Fails
+/// with `InvalidInput` if `s` is longer than `MAX_PROTOCOL_STRING_LENGTH`.
+pub fn send_protocol_string<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
+    if s.len() > MAX_PROTOCOL_STRING_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "protocol string too long",
+        ));
+    }
+    // The cost of sending two strings outweighs the cost of formatting.
+    writer.write_all(format!("{:04x}{}", s.len(), s).as_bytes())
+}
+
+/// Reads a protocol-format string; a four hex digit length followed by the
+/// string data.
+///
+/// This is a port of `ReadProtocolString` in `original/adb_io.cpp`. Fails
+/// with `InvalidData` if the length is not valid hex or the data is not valid
+/// UTF-8, and with `UnexpectedEof` if the stream ends early.
+pub fn read_protocol_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_protocol_length(reader)?;
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes `value` as a protocol-format string holding its decimal text.
+///
+/// This is how adb replies with integers, e.g. the port a `forward` or
+/// `reverse` to `tcp:0` resolved to in `original/adb.cpp`. The integer is
+/// sent as ASCII digits rather than as binary, so it has no endianness:
+/// `8080` is sent as `00048080`.
+pub fn send_protocol_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    send_protocol_string(writer, &value.to_string())
+}
+
+/// Reads an integer written by `send_protocol_u32`; a protocol-format string
+/// holding its decimal text.
+///
+/// Fails with `InvalidData` if the string is not a decimal `u32`.
+pub fn read_protocol_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let s = read_protocol_string(reader)?;
+    s.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid protocol integer {:?}", s),
+        )
+    })
+}
+
+/// Reads a protocol-format string like `read_protocol_string`, but fails
+/// with `InvalidData` if the declared length exceeds `max_len`.
+///
+/// The length is checked before anything is allocated or read, so an
+/// untrusted peer can't make the reader buffer more than `max_len` bytes.
+pub fn read_protocol_string_limited<R: Read>(reader: &mut R, max_len: usize) -> io::Result<String> {
+    let len = read_protocol_length(reader)?;
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("protocol string length {} exceeds limit {}", len, max_len),
+        ));
+    }
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads a protocol-format string like `read_protocol_string`, but passes
+/// the string data to `sink` in chunks as it arrives instead of buffering
+/// all of it.
+///
+/// This avoids allocating the whole string up front when reading from an
+/// untrusted peer. If `sink` returns an error, reading stops and the error
+/// is returned, leaving the rest of the string unread.
+///
+/// Returns the length of the string.
+pub fn read_protocol_string_streaming<R, F>(reader: &mut R, mut sink: F) -> io::Result<usize>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let len = read_protocol_length(reader)?;
+    let mut buf = [0u8; STREAMING_CHUNK_SIZE];
+    let mut remaining = len;
+    while remaining > 0 {
+        let want = remaining.min(STREAMING_CHUNK_SIZE);
+        let n = match reader.read(&mut buf[..want]) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        sink(&buf[..n])?;
+        remaining -= n;
+    }
+    Ok(len)
+}
+
+/// An iterator over the protocol strings read from a stream.
+///
+/// Yields each string as `read_protocol_string` would, and ends when the
+/// stream ends cleanly between two strings. A stream ending partway through
+/// a string yields an `UnexpectedEof` error. The iterator ends after the
+/// first error, since the stream is no longer at a string boundary.
+///
+/// ```
+/// # use adb_io::ProtocolStringIter;
+/// let input: &[u8] = b"0002hi0005there";
+/// let strings: Vec<String> = ProtocolStringIter::new(input)
+///     .collect::<std::io::Result<_>>()
+///     .unwrap();
+/// assert_eq!(vec!["hi", "there"], strings);
+/// ```
+pub struct ProtocolStringIter<R> {
+    reader: R,
+    done: bool,
+}
+
+impl<R: Read> ProtocolStringIter<R> {
+    /// Creates an iterator over the protocol strings read from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            done: false,
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_next(&mut self) -> io::Result<Option<String>> {
+        // Only an EOF before the first byte of the length is a clean end.
+        let mut buf = [0u8; 4];
+        loop {
+            match self.reader.read(&mut buf[..1]) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.reader.read_exact(&mut buf[1..])?;
+        let len = parse_protocol_length(&buf)?;
+        let mut data = vec![0; len];
+        self.reader.read_exact(&mut data)?;
+        String::from_utf8(data)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<R: Read> Iterator for ProtocolStringIter<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_next().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// Reads the four hex digit length prefix of a protocol string.
+fn read_protocol_length<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    parse_protocol_length(&buf)
+}
+
+/// Parses the four hex digit length prefix of a protocol string.
+pub(crate) fn parse_protocol_length(buf: &[u8; 4]) -> io::Result<usize> {
+    std::str::from_utf8(buf)
+        .ok()
+        .and_then(|s| usize::from_str_radix(s, 16).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid protocol string length"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trip() {
+        let mut buf = Vec::new();
+        send_protocol_string(&mut buf, "host:version").unwrap();
+        assert_eq!(b"000chost:version", buf.as_slice());
+
+        let s = read_protocol_string(&mut Cursor::new(buf)).unwrap();
+        assert_eq!("host:version", s);
+    }
+
+    #[test]
+    fn u32_round_trip() {
+        let mut buf = Vec::new();
+        send_protocol_u32(&mut buf, 8080).unwrap();
+        send_protocol_u32(&mut buf, 0).unwrap();
+        send_protocol_u32(&mut buf, u32::MAX).unwrap();
+        assert_eq!(b"0004808000010000a4294967295", buf.as_slice());
+
+        let mut cursor = Cursor::new(buf);
+        assert_eq!(8080, read_protocol_u32(&mut cursor).unwrap());
+        assert_eq!(0, read_protocol_u32(&mut cursor).unwrap());
+        assert_eq!(u32::MAX, read_protocol_u32(&mut cursor).unwrap());
+
+        for bad in [&b"0000"[..], b"0003abc", b"000a4294967296", b"0002-1"] {
+            let err = read_protocol_u32(&mut Cursor::new(bad)).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+    }
+
+    #[test]
+    fn read_limited() {
+        let s = read_protocol_string_limited(&mut Cursor::new(b"0005hello"), 5).unwrap();
+        assert_eq!("hello", s);
+
+        // Only the length is consumed, the data is left unread.
+        let mut cursor = Cursor::new(b"ffffhello");
+        let err = read_protocol_string_limited(&mut cursor, 16).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!(4, cursor.position());
+
+        let err = read_protocol_string_limited(&mut Cursor::new(b"0006hello"), 5).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn send_too_long() {
+        let s = "x".repeat(MAX_PROTOCOL_STRING_LENGTH + 1);
+        let err = send_protocol_string(&mut Vec::new(), &s).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn read_invalid_length() {
+        let err = read_protocol_string(&mut Cursor::new(b"00g1x")).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn read_short() {
+        let err = read_protocol_string(&mut Cursor::new(b"0005abc")).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+        let err = read_protocol_string(&mut Cursor::new(b"00")).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn iter() {
+        let mut buf = Vec::new();
+        send_protocol_string(&mut buf, "host:version").unwrap();
+        send_protocol_string(&mut buf, "").unwrap();
+
+        let mut iter = ProtocolStringIter::new(Cursor::new(buf));
+        assert_eq!("host:version", iter.next().unwrap().unwrap());
+        assert_eq!("", iter.next().unwrap().unwrap());
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn iter_short() {
+        let mut iter = ProtocolStringIter::new(Cursor::new(b"0002hi00"));
+        assert_eq!("hi", iter.next().unwrap().unwrap());
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn streaming() {
+        let expected = "0123456789".repeat(1000);
+        let mut buf = Vec::new();
+        send_protocol_string(&mut buf, &expected).unwrap();
+
+        let mut chunks = Vec::new();
+        let len = read_protocol_string_streaming(&mut Cursor::new(buf), |chunk| {
+            chunks.push(chunk.to_vec());
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(expected.len(), len);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.len() <= STREAMING_CHUNK_SIZE));
+        assert_eq!(expected.as_bytes(), chunks.concat().as_slice());
+    }
+
+    #[test]
+    fn streaming_abort() {
+        let mut buf = Vec::new();
+        send_protocol_string(&mut buf, &"x".repeat(10000)).unwrap();
+
+        let mut received = 0;
+        let err = read_protocol_string_streaming(&mut Cursor::new(buf), |chunk| {
+            received += chunk.len();
+            Err(io::Error::other("abort"))
+        })
+        .unwrap_err();
+        assert_eq!(io::ErrorKind::Other, err.kind());
+        assert_eq!(STREAMING_CHUNK_SIZE, received);
+    }
+
+    #[test]
+    fn streaming_short() {
+        let err =
+            read_protocol_string_streaming(&mut Cursor::new(b"0005abc"), |_| Ok(())).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::{self, Write};
+use std::net::Shutdown;
+use std::os::fd::AsFd;
+
+/// Flushes `stream`, then shuts down its write side so that the peer reads
+/// EOF, while the read side stays open for the peer's remaining data.
+///
+/// This is what a client does after sending its final packet.
+pub fn shutdown_write<S: Write + AsFd>(stream: &mut S) -> io::Result<()> {
+    stream.flush()?;
+    sysdeps::shutdown(stream, Shutdown::Write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufWriter, Read};
+    use std::os::unix::net::UnixStream;
+
+    /// Lets a buffered writer be passed to `shutdown_write`, to check that
+    /// the buffered data is flushed first.
+    struct Buffered(BufWriter<UnixStream>);
+
+    impl Write for Buffered {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    impl AsFd for Buffered {
+        fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+            self.0.get_ref().as_fd()
+        }
+    }
+
+    #[test]
+    fn peer_reads_eof() {
+        let (a, mut b) = UnixStream::pair().unwrap();
+        let mut a = Buffered(BufWriter::new(a));
+        a.write_all(b"CLSE").unwrap();
+        shutdown_write(&mut a).unwrap();
+
+        let mut buf = Vec::new();
+        b.read_to_end(&mut buf).unwrap();
+        assert_eq!(b"CLSE", buf.as_slice());
+
+        // The read side of the stream is still open.
+        b.write_all(b"OKAY").unwrap();
+        let mut buf = [0u8; 4];
+        a.0.get_mut().read_exact(&mut buf).unwrap();
+        assert_eq!(b"OKAY", &buf);
+    }
+}
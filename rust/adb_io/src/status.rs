@@ -0,0 +1,90 @@
+use crate::{read_protocol_string, send_protocol_string};
+use std::io::{self, Read, Write};
+
+/// The status a smart socket peer responds to a request with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// The request succeeded.
+    Okay,
+    /// The request failed, with the reason given by the peer.
+    Fail(String),
+}
+
+/// Writes an `OKAY` status.
+///
+/// This is a port of `SendOkay` in `original/adb_io.cpp`.
+pub fn send_okay<W: Write>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(b"OKAY")
+}
+
+/// Writes a `FAIL` status followed by `reason` as a protocol string.
+///
+/// This is a port of `SendFail` in `original/adb_io.cpp`.
+pub fn send_fail<W: Write>(writer: &mut W, reason: &str) -> io::Result<()> {
+    writer.write_all(b"FAIL")?;
+    send_protocol_string(writer, reason)
+}
+
+/// Reads the status a peer responded with; `OKAY`, or `FAIL` followed by
+/// the reason as a protocol string.
+///
+/// This is a port of `adb_status` in `original/client/adb_client.cpp`. Fails
+/// with `InvalidData` if the status is neither.
+pub fn read_status<R: Read>(reader: &mut R) -> io::Result<Status> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    match &buf {
+        b"OKAY" => Ok(Status::Okay),
+        b"FAIL" => Ok(Status::Fail(read_protocol_string(reader)?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "protocol fault (status {:02x} {:02x} {:02x} {:02x}?!)",
+                buf[0], buf[1], buf[2], buf[3]
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_okay() {
+        assert_eq!(
+            Status::Okay,
+            read_status(&mut Cursor::new(b"OKAY")).unwrap()
+        );
+    }
+
+    #[test]
+    fn read_fail() {
+        let status = read_status(&mut Cursor::new(b"FAIL0005error")).unwrap();
+        assert_eq!(Status::Fail("error".to_string()), status);
+    }
+
+    #[test]
+    fn read_invalid() {
+        let err = read_status(&mut Cursor::new(b"WHAT")).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!("protocol fault (status 57 48 41 54?!)", err.to_string());
+
+        let err = read_status(&mut Cursor::new(b"OK")).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = Vec::new();
+        send_okay(&mut buf).unwrap();
+        send_fail(&mut buf, "device offline").unwrap();
+        assert_eq!(b"OKAYFAIL000edevice offline", buf.as_slice());
+
+        let mut cursor = Cursor::new(buf);
+        assert_eq!(Status::Okay, read_status(&mut cursor).unwrap());
+        let status = read_status(&mut cursor).unwrap();
+        assert_eq!(Status::Fail("device offline".to_string()), status);
+    }
+}
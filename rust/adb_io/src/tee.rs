@@ -0,0 +1,145 @@
+use crate::dump_hex;
+use std::io::{self, Read, Write};
+use trace::{adb_trace, AdbTrace};
+
+/// A reader that passes data through from `inner`, tracing a hex dump of
+/// every read at the `packets` tag.
+///
+/// When created with `with_capture`, all the bytes read are also retained and
+/// available from `captured`.
+pub struct TeeReader<R> {
+    inner: R,
+    captured: Option<Vec<u8>>,
+}
+
+impl<R> TeeReader<R> {
+    /// Wraps `inner`, tracing the data read from it.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            captured: None,
+        }
+    }
+
+    /// Wraps `inner`, tracing and capturing the data read from it.
+    pub fn with_capture(inner: R) -> Self {
+        Self {
+            inner,
+            captured: Some(Vec::new()),
+        }
+    }
+
+    /// Returns the bytes read so far, or an empty slice if capturing is not
+    /// enabled.
+    pub fn captured(&self) -> &[u8] {
+        self.captured.as_deref().unwrap_or_default()
+    }
+
+    /// Unwraps this `TeeReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for TeeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        adb_trace!(AdbTrace::Packets, "read: {}", dump_hex(&buf[..len]));
+        if let Some(captured) = &mut self.captured {
+            captured.extend_from_slice(&buf[..len]);
+        }
+        Ok(len)
+    }
+}
+
+/// A writer that passes data through to `inner`, tracing a hex dump of
+/// every write at the `packets` tag.
+///
+/// When created with `with_capture`, all the bytes written are also retained
+/// and available from `captured`.
+pub struct TeeWriter<W> {
+    inner: W,
+    captured: Option<Vec<u8>>,
+}
+
+impl<W> TeeWriter<W> {
+    /// Wraps `inner`, tracing the data written to it.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            captured: None,
+        }
+    }
+
+    /// Wraps `inner`, tracing and capturing the data written to it.
+    pub fn with_capture(inner: W) -> Self {
+        Self {
+            inner,
+            captured: Some(Vec::new()),
+        }
+    }
+
+    /// Returns the bytes written so far, or an empty slice if capturing is
+    /// not enabled.
+    pub fn captured(&self) -> &[u8] {
+        self.captured.as_deref().unwrap_or_default()
+    }
+
+    /// Unwraps this `TeeWriter`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for TeeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        adb_trace!(AdbTrace::Packets, "write: {}", dump_hex(&buf[..len]));
+        if let Some(captured) = &mut self.captured {
+            captured.extend_from_slice(&buf[..len]);
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn tee_reader() {
+        let mut reader = TeeReader::with_capture(Cursor::new(b"0004host".to_vec()));
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+
+        assert_eq!(b"0004", &len);
+        assert_eq!(b"host", rest.as_slice());
+        assert_eq!(b"0004host", reader.captured());
+    }
+
+    #[test]
+    fn tee_writer() {
+        let mut writer = TeeWriter::with_capture(Vec::new());
+        writer.write_all(b"OKAY").unwrap();
+        writer.write_all(b"0000").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(b"OKAY0000", writer.captured());
+        assert_eq!(b"OKAY0000", writer.into_inner().as_slice());
+    }
+
+    #[test]
+    fn tee_without_capture() {
+        let mut writer = TeeWriter::new(Vec::new());
+        writer.write_all(b"OKAY").unwrap();
+        assert!(writer.captured().is_empty());
+        assert_eq!(b"OKAY", writer.into_inner().as_slice());
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{self, IoSlice, Write};
+
+/// Writes all of `bufs` to `writer` using vectored writes, so that e.g. a
+/// header and a payload can go out in a single syscall.
+///
+/// `Write::write_vectored` may write only part of the data, so this keeps
+/// calling it, advancing past whatever was written, until every slice has
+/// been written. Fails with `WriteZero` if the writer stops accepting data.
+pub fn write_vectored_all<W: Write>(writer: &mut W, bufs: &[IoSlice]) -> io::Result<()> {
+    let mut bufs: Vec<IoSlice> = bufs.to_vec();
+    let mut bufs = &mut bufs[..];
+    // Skip leading empty slices so that an empty input doesn't call the
+    // writer at all.
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer which accepts at most `limit` bytes per call, the way a
+    /// socket with a small send buffer might.
+    struct TrickleWriter {
+        data: Vec<u8>,
+        limit: usize,
+        calls: usize,
+    }
+
+    impl Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            let n = buf.len().min(self.limit);
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+            self.calls += 1;
+            let mut written = 0;
+            for buf in bufs {
+                let n = buf.len().min(self.limit - written);
+                self.data.extend_from_slice(&buf[..n]);
+                written += n;
+                if written == self.limit {
+                    break;
+                }
+            }
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn partial_writes() {
+        let mut writer = TrickleWriter {
+            data: Vec::new(),
+            limit: 3,
+            calls: 0,
+        };
+        let bufs = [
+            IoSlice::new(b"header"),
+            IoSlice::new(b""),
+            IoSlice::new(b"payload"),
+        ];
+        write_vectored_all(&mut writer, &bufs).unwrap();
+        assert_eq!(b"headerpayload", writer.data.as_slice());
+        assert_eq!(5, writer.calls);
+    }
+
+    #[test]
+    fn empty() {
+        let mut writer = TrickleWriter {
+            data: Vec::new(),
+            limit: 3,
+            calls: 0,
+        };
+        write_vectored_all(&mut writer, &[]).unwrap();
+        write_vectored_all(&mut writer, &[IoSlice::new(b"")]).unwrap();
+        assert_eq!(0, writer.calls);
+    }
+
+    #[test]
+    fn write_zero() {
+        let mut buf = [0u8; 4];
+        let mut writer = &mut buf[..];
+        let err = write_vectored_all(&mut writer, &[IoSlice::new(b"foo"), IoSlice::new(b"bar")])
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::WriteZero, err.kind());
+    }
+}
@@ -0,0 +1,106 @@
+//! Non-RSA keys for TLS certificates.
+//!
+//! adb auth only works with the RSA `Key`, but the certificates used for
+//! pairing and TLS can be signed with any key the peer supports.
+
+use anyhow::Result;
+
+use crate::CertificateKey;
+
+/// The algorithm of an `EcKey`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EcAlgorithm {
+    /// ECDSA over the NIST P-256 curve, with SHA-256.
+    P256,
+    /// EdDSA over Curve25519.
+    Ed25519,
+}
+
+impl EcAlgorithm {
+    fn signature_algorithm(self) -> &'static rcgen::SignatureAlgorithm {
+        match self {
+            EcAlgorithm::P256 => &rcgen::PKCS_ECDSA_P256_SHA256,
+            EcAlgorithm::Ed25519 => &rcgen::PKCS_ED25519,
+        }
+    }
+}
+
+/// A P-256 or Ed25519 private key, which can sign a certificate in place of
+/// an RSA `Key`.
+pub struct EcKey {
+    algorithm: EcAlgorithm,
+    pkcs8: Vec<u8>,
+}
+
+impl EcKey {
+    /// Generate a new random key.
+    pub fn generate(algorithm: EcAlgorithm) -> Result<EcKey> {
+        let key_pair = rcgen::KeyPair::generate(algorithm.signature_algorithm())?;
+        Ok(EcKey {
+            algorithm,
+            pkcs8: key_pair.serialize_der(),
+        })
+    }
+
+    /// Load a key of the given algorithm from its PKCS#8 DER encoding.
+    pub fn from_pkcs8_der(algorithm: EcAlgorithm, der: &[u8]) -> Result<EcKey> {
+        // Parse the key once to reject a mismatched algorithm early.
+        rcgen::KeyPair::from_der_and_sign_algo(der, algorithm.signature_algorithm())?;
+        Ok(EcKey {
+            algorithm,
+            pkcs8: der.to_vec(),
+        })
+    }
+
+    pub fn algorithm(&self) -> EcAlgorithm {
+        self.algorithm
+    }
+
+    /// Return the private key in its PKCS#8 DER encoding.
+    pub fn to_pkcs8_der(&self) -> &[u8] {
+        &self.pkcs8
+    }
+}
+
+impl CertificateKey for EcKey {
+    fn signature_algorithm(&self) -> &'static rcgen::SignatureAlgorithm {
+        self.algorithm.signature_algorithm()
+    }
+
+    fn to_key_pair(&self) -> Result<rcgen::KeyPair> {
+        Ok(rcgen::KeyPair::from_der_and_sign_algo(
+            &self.pkcs8,
+            self.signature_algorithm(),
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_x509_certificate;
+    use x509_parser::prelude::{FromDer, X509Certificate};
+
+    #[test]
+    fn self_signed_certificate() {
+        for algorithm in [EcAlgorithm::P256, EcAlgorithm::Ed25519] {
+            let key = EcKey::generate(algorithm).unwrap();
+            assert_eq!(algorithm, key.algorithm());
+            let der = generate_x509_certificate(&key)
+                .unwrap()
+                .serialize_der()
+                .unwrap();
+            let (_, cert) = X509Certificate::from_der(&der).unwrap();
+            cert.verify_signature(None).unwrap();
+
+            let loaded = EcKey::from_pkcs8_der(algorithm, key.to_pkcs8_der()).unwrap();
+            assert_eq!(key.to_pkcs8_der(), loaded.to_pkcs8_der());
+        }
+    }
+
+    #[test]
+    fn from_pkcs8_der_wrong_algorithm() {
+        let key = EcKey::generate(EcAlgorithm::P256).unwrap();
+        assert!(EcKey::from_pkcs8_der(EcAlgorithm::Ed25519, key.to_pkcs8_der()).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+//! The key and certificate adb presents on a TLS connection.
+//!
+//! adb certificates are self-signed, so they can't be checked against a
+//! trust store. Instead, a client pins the fingerprint of the device
+//! certificate it learned when pairing, and adbd accepts any client
+//! certificate and decides afterwards whether the key in it is authorized.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::{generate_x509_certificate, Key};
+
+/// A private key along with the self-signed certificate for it.
+pub struct TlsIdentity {
+    key: Key,
+    cert_der: Vec<u8>,
+}
+
+impl TlsIdentity {
+    /// Creates an identity for `key`, with a certificate from
+    /// `generate_x509_certificate`.
+    pub fn new(key: Key) -> Result<TlsIdentity> {
+        let cert_der = generate_x509_certificate(&key)?.serialize_der()?;
+        Ok(TlsIdentity { key, cert_der })
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    /// The DER encoding of the certificate.
+    pub fn cert_der(&self) -> &[u8] {
+        &self.cert_der
+    }
+
+    /// The SHA-256 hash of the certificate, which the peer pins with
+    /// `rustls_client_config_with_pinned_cert`.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(&self.cert_der).into()
+    }
+}
+
+#[cfg(feature = "rustls")]
+mod rustls_config {
+    use super::TlsIdentity;
+    use anyhow::Result;
+    use rsa::pkcs8::EncodePrivateKey;
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::crypto::{ring, WebPkiSupportedAlgorithms};
+    use rustls::pki_types::{
+        CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime,
+    };
+    use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+    use rustls::{
+        CertificateError, ClientConfig, DigitallySignedStruct, DistinguishedName, ServerConfig,
+        SignatureScheme,
+    };
+    use sha2::{Digest, Sha256};
+    use std::sync::Arc;
+
+    // adb only speaks TLS 1.3, see `original/tls/tls_connection.cpp`.
+    static PROTOCOL_VERSIONS: &[&rustls::SupportedProtocolVersion] = &[&rustls::version::TLS13];
+
+    impl TlsIdentity {
+        /// Returns a config for the adbd side of a connection.
+        ///
+        /// The config requires a client certificate but accepts any, as long
+        /// as the client proves it holds the key. Whether that key is allowed
+        /// to connect is up to the caller.
+        pub fn rustls_server_config(&self) -> Result<ServerConfig> {
+            let provider = Arc::new(ring::default_provider());
+            let verifier = Arc::new(AnyClientCert {
+                algorithms: provider.signature_verification_algorithms,
+            });
+            let config = ServerConfig::builder_with_provider(provider)
+                .with_protocol_versions(PROTOCOL_VERSIONS)?
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(self.cert_chain(), self.private_key_der()?)?;
+            Ok(config)
+        }
+
+        /// Returns a config for the adb client side of a connection, which
+        /// presents this identity and only accepts a server whose certificate
+        /// has the SHA-256 fingerprint `peer_fingerprint`.
+        ///
+        /// The server name passed to `rustls::ClientConnection::new` is
+        /// ignored.
+        pub fn rustls_client_config_with_pinned_cert(
+            &self,
+            peer_fingerprint: [u8; 32],
+        ) -> Result<ClientConfig> {
+            let provider = Arc::new(ring::default_provider());
+            let verifier = Arc::new(PinnedServerCert {
+                fingerprint: peer_fingerprint,
+                algorithms: provider.signature_verification_algorithms,
+            });
+            let config = ClientConfig::builder_with_provider(provider)
+                .with_protocol_versions(PROTOCOL_VERSIONS)?
+                .dangerous()
+                .with_custom_certificate_verifier(verifier)
+                .with_client_auth_cert(self.cert_chain(), self.private_key_der()?)?;
+            Ok(config)
+        }
+
+        fn cert_chain(&self) -> Vec<CertificateDer<'static>> {
+            vec![CertificateDer::from(self.cert_der.clone())]
+        }
+
+        fn private_key_der(&self) -> Result<PrivateKeyDer<'static>> {
+            let der = self.key.0.to_pkcs8_der()?;
+            Ok(PrivatePkcs8KeyDer::from(der.as_bytes().to_vec()).into())
+        }
+    }
+
+    #[derive(Debug)]
+    struct AnyClientCert {
+        algorithms: WebPkiSupportedAlgorithms,
+    }
+
+    impl ClientCertVerifier for AnyClientCert {
+        fn root_hint_subjects(&self) -> &[DistinguishedName] {
+            &[]
+        }
+
+        fn verify_client_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _now: UnixTime,
+        ) -> Result<ClientCertVerified, rustls::Error> {
+            Ok(ClientCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.algorithms.supported_schemes()
+        }
+    }
+
+    #[derive(Debug)]
+    struct PinnedServerCert {
+        fingerprint: [u8; 32],
+        algorithms: WebPkiSupportedAlgorithms,
+    }
+
+    impl ServerCertVerifier for PinnedServerCert {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            let fingerprint: [u8; 32] = Sha256::digest(end_entity).into();
+            if fingerprint != self.fingerprint {
+                return Err(CertificateError::ApplicationVerificationFailure.into());
+            }
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.algorithms.supported_schemes()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod tests {
+    use super::*;
+    use crate::new_rsa_2048;
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConnection, ServerConnection, StreamOwned};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Runs a server for `server` on a loopback port and connects to it with
+    /// a client for `client` pinning `pin`. Returns what the client read back
+    /// from the echo server.
+    fn echo_over_tls(
+        server: TlsIdentity,
+        client: &TlsIdentity,
+        pin: [u8; 32],
+    ) -> std::io::Result<Vec<u8>> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server_config = Arc::new(server.rustls_server_config().unwrap());
+        let server_thread = thread::spawn(move || -> std::io::Result<()> {
+            let (socket, _) = listener.accept()?;
+            let conn = ServerConnection::new(server_config).unwrap();
+            let mut stream = StreamOwned::new(conn, socket);
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf)?;
+            stream.write_all(&buf)?;
+            stream.flush()
+        });
+
+        let client_config = Arc::new(client.rustls_client_config_with_pinned_cert(pin).unwrap());
+        let name = ServerName::try_from("adb").unwrap();
+        let conn = ClientConnection::new(client_config, name).unwrap();
+        let socket = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut stream = StreamOwned::new(conn, socket);
+        let result = stream.write_all(b"hello").and_then(|()| {
+            let mut buf = vec![0u8; 5];
+            stream.read_exact(&mut buf)?;
+            Ok(buf)
+        });
+        drop(stream);
+        let _ = server_thread.join().unwrap();
+        result
+    }
+
+    #[test]
+    fn loopback_session_with_pinned_cert() {
+        let server = TlsIdentity::new(new_rsa_2048().unwrap()).unwrap();
+        let client = TlsIdentity::new(new_rsa_2048().unwrap()).unwrap();
+        let pin = server.fingerprint();
+        assert_eq!(echo_over_tls(server, &client, pin).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn loopback_session_rejects_wrong_pin() {
+        let server = TlsIdentity::new(new_rsa_2048().unwrap()).unwrap();
+        let client = TlsIdentity::new(new_rsa_2048().unwrap()).unwrap();
+        let pin = client.fingerprint();
+        assert!(echo_over_tls(server, &client, pin).is_err());
+    }
+}
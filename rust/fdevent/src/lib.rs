@@ -0,0 +1,365 @@
+//! A Rust port of the adb `fdevent` event loop.
+//!
+//! The C++ implementation in `original/fdevent` carries its own epoll and
+//! poll backends. This port delegates the platform specifics to `mio` and
+//! only keeps track of which handler belongs to which file descriptor.
+//!
+//! # Usage
+//!
+//! ```no_run
+//! use fdevent::{Fdevent, Interest};
+//! use std::os::unix::net::UnixStream;
+//!
+//! let (a, _b) = UnixStream::pair().unwrap();
+//! let mut fdevent = Fdevent::new().unwrap();
+//! fdevent
+//!     .register(&a, |event| println!("readable: {}", event.is_readable()), Interest::READABLE)
+//!     .unwrap();
+//! fdevent.run_once(None).unwrap();
+//! ```
+//!
+//! Only Unix is supported for now, since handlers are keyed by raw file
+//! descriptors.
+
+#![cfg(unix)]
+
+use mio::unix::SourceFd;
+use mio::{Events, Poll};
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+pub use mio::event::Event;
+pub use mio::{Interest, Token};
+
+/// The default number of events that can be reported by a single call to
+/// `run_once`.
+pub const DEFAULT_EVENTS_CAPACITY: usize = 1024;
+
+/// A callback invoked when a registered file descriptor becomes ready.
+pub type Handler = Box<dyn FnMut(&Event)>;
+
+/// A file descriptor registered with the event loop, and its handler.
+struct Registration {
+    fd: RawFd,
+    handler: Handler,
+}
+
+/// An event loop dispatching readiness events to per-fd handlers.
+///
+/// This is the equivalent of the C++ `fdevent_context`. Unlike the C++
+/// version, the `Fdevent` does not take ownership of the file descriptors
+/// registered with it. Callers must unregister a file descriptor before
+/// closing it.
+pub struct Fdevent {
+    poll: Poll,
+    events: Events,
+    registrations: HashMap<Token, Registration>,
+    next_token: usize,
+}
+
+impl Fdevent {
+    /// Creates a new, empty event loop.
+    pub fn new() -> io::Result<Self> {
+        Self::with_capacity(DEFAULT_EVENTS_CAPACITY)
+    }
+
+    /// Creates a new, empty event loop, which reports at most `capacity`
+    /// events per call to `run_once`. Events that don't fit are reported by
+    /// subsequent calls.
+    ///
+    /// The event buffer is allocated once and reused for every iteration.
+    pub fn with_capacity(capacity: usize) -> io::Result<Self> {
+        Ok(Self {
+            poll: Poll::new()?,
+            events: Events::with_capacity(capacity),
+            registrations: HashMap::new(),
+            next_token: 0,
+        })
+    }
+
+    /// Registers `source` with the event loop, invoking `handler` whenever it
+    /// becomes ready for any of the events in `interest`.
+    ///
+    /// Returns the token identifying this registration.
+    pub fn register<T, F>(
+        &mut self,
+        source: &T,
+        handler: F,
+        interest: Interest,
+    ) -> io::Result<Token>
+    where
+        T: AsRawFd,
+        F: FnMut(&Event) + 'static,
+    {
+        self.register_raw(source.as_raw_fd(), handler, interest)
+    }
+
+    /// Registers a bare file descriptor with the event loop.
+    ///
+    /// This is useful for file descriptors that aren't wrapped in a type
+    /// implementing `AsRawFd`, such as the ends of a `pipe()`.
+    pub fn register_raw<F>(
+        &mut self,
+        fd: RawFd,
+        handler: F,
+        interest: Interest,
+    ) -> io::Result<Token>
+    where
+        F: FnMut(&Event) + 'static,
+    {
+        let token = Token(self.next_token);
+        self.poll
+            .registry()
+            .register(&mut SourceFd(&fd), token, interest)?;
+        self.next_token += 1;
+        self.registrations.insert(
+            token,
+            Registration {
+                fd,
+                handler: Box::new(handler),
+            },
+        );
+        Ok(token)
+    }
+
+    /// Changes the events `source` is registered for.
+    pub fn reregister<T: AsRawFd>(
+        &mut self,
+        source: &T,
+        token: Token,
+        interest: Interest,
+    ) -> io::Result<()> {
+        self.reregister_raw(source.as_raw_fd(), token, interest)
+    }
+
+    /// Changes the events a bare file descriptor is registered for.
+    pub fn reregister_raw(
+        &mut self,
+        fd: RawFd,
+        token: Token,
+        interest: Interest,
+    ) -> io::Result<()> {
+        self.poll
+            .registry()
+            .reregister(&mut SourceFd(&fd), token, interest)
+    }
+
+    /// Removes `source` from the event loop and drops its handler.
+    pub fn unregister<T: AsRawFd>(&mut self, source: &T, token: Token) -> io::Result<()> {
+        self.unregister_raw(source.as_raw_fd(), token)
+    }
+
+    /// Removes a bare file descriptor from the event loop and drops its
+    /// handler.
+    ///
+    /// Fails with `InvalidInput` if `token` isn't registered for `fd`. If the
+    /// file descriptor can't be removed, the handler is kept, so that its
+    /// events are still dispatched.
+    pub fn unregister_raw(&mut self, fd: RawFd, token: Token) -> io::Result<()> {
+        if self.fd_for(token) != Some(fd) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{token:?} isn't registered for fd {fd}"),
+            ));
+        }
+        self.poll.registry().deregister(&mut SourceFd(&fd))?;
+        self.registrations.remove(&token);
+        Ok(())
+    }
+
+    /// Waits for at most `timeout` (or forever if `None`) for events, and
+    /// dispatches them to their handlers.
+    ///
+    /// Returns the number of events dispatched.
+    pub fn run_once(&mut self, timeout: Option<Duration>) -> io::Result<usize> {
+        match self.poll.poll(&mut self.events, timeout) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(0),
+            Err(e) => return Err(e),
+        }
+
+        let mut dispatched = 0;
+        for event in self.events.iter() {
+            if let Some(registration) = self.registrations.get_mut(&event.token()) {
+                (registration.handler)(event);
+                dispatched += 1;
+            }
+        }
+        Ok(dispatched)
+    }
+
+    /// Returns the maximum number of events dispatched per call to
+    /// `run_once`.
+    pub fn events_capacity(&self) -> usize {
+        self.events.capacity()
+    }
+
+    /// Returns the number of file descriptors currently registered.
+    pub fn installed_count(&self) -> usize {
+        self.registrations.len()
+    }
+
+    /// Returns the tokens of all current registrations, in no particular
+    /// order.
+    ///
+    /// This is intended for diagnostics, e.g. to list the open connections
+    /// or to detect leaked registrations.
+    pub fn tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        self.registrations.keys().copied()
+    }
+
+    /// Returns the file descriptor registered with `token`, or `None` if
+    /// there is no such registration.
+    pub fn fd_for(&self, token: Token) -> Option<RawFd> {
+        self.registrations
+            .get(&token)
+            .map(|registration| registration.fd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+    use std::rc::Rc;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn register_stream() {
+        let (a, mut b) = UnixStream::pair().unwrap();
+        let mut fdevent = Fdevent::new().unwrap();
+
+        let readable = Rc::new(RefCell::new(false));
+        let readable_clone = readable.clone();
+        let token = fdevent
+            .register(
+                &a,
+                move |event| *readable_clone.borrow_mut() = event.is_readable(),
+                Interest::READABLE,
+            )
+            .unwrap();
+        assert_eq!(1, fdevent.installed_count());
+
+        b.write_all(b"x").unwrap();
+        assert_eq!(1, fdevent.run_once(Some(TIMEOUT)).unwrap());
+        assert!(*readable.borrow());
+
+        fdevent.unregister(&a, token).unwrap();
+        assert_eq!(0, fdevent.installed_count());
+    }
+
+    #[test]
+    fn register_raw_pipe() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let mut fdevent = Fdevent::new().unwrap();
+
+        let tokens = Rc::new(RefCell::new(Vec::new()));
+        let tokens_clone = tokens.clone();
+        let token = fdevent
+            .register_raw(
+                reader.as_raw_fd(),
+                move |event| {
+                    assert!(event.is_readable());
+                    tokens_clone.borrow_mut().push(event.token());
+                },
+                Interest::READABLE,
+            )
+            .unwrap();
+
+        writer.write_all(b"x").unwrap();
+        assert_eq!(1, fdevent.run_once(Some(TIMEOUT)).unwrap());
+        assert_eq!(vec![token], *tokens.borrow());
+
+        fdevent
+            .reregister_raw(reader.as_raw_fd(), token, Interest::READABLE)
+            .unwrap();
+        // A token registered for another file descriptor is rejected, and
+        // both registrations are kept.
+        let (other, _other_writer) = std::io::pipe().unwrap();
+        let other_fd = other.as_raw_fd();
+        let other_token = fdevent
+            .register_raw(other_fd, |_| {}, Interest::READABLE)
+            .unwrap();
+        let err = fdevent.unregister_raw(other_fd, token).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!(2, fdevent.installed_count());
+        assert_eq!(Some(reader.as_raw_fd()), fdevent.fd_for(token));
+        assert_eq!(Some(other_fd), fdevent.fd_for(other_token));
+        assert_eq!(1, fdevent.run_once(Some(TIMEOUT)).unwrap());
+        assert_eq!(vec![token, token], *tokens.borrow());
+
+        // Failing to deregister keeps the handler.
+        drop(other);
+        assert!(fdevent.unregister_raw(other_fd, other_token).is_err());
+        assert_eq!(Some(other_fd), fdevent.fd_for(other_token));
+
+        fdevent.unregister_raw(reader.as_raw_fd(), token).unwrap();
+        assert_eq!(1, fdevent.installed_count());
+    }
+
+    #[test]
+    fn with_capacity() {
+        let (a, mut a_peer) = UnixStream::pair().unwrap();
+        let (b, mut b_peer) = UnixStream::pair().unwrap();
+        let mut fdevent = Fdevent::with_capacity(1).unwrap();
+        assert_eq!(1, fdevent.events_capacity());
+        assert_eq!(
+            DEFAULT_EVENTS_CAPACITY,
+            Fdevent::new().unwrap().events_capacity()
+        );
+
+        let dispatched = Rc::new(RefCell::new(Vec::new()));
+        let dispatched_a = dispatched.clone();
+        let token_a = fdevent
+            .register(
+                &a,
+                move |event| dispatched_a.borrow_mut().push(event.token()),
+                Interest::READABLE,
+            )
+            .unwrap();
+        let dispatched_b = dispatched.clone();
+        let token_b = fdevent
+            .register(
+                &b,
+                move |event| dispatched_b.borrow_mut().push(event.token()),
+                Interest::READABLE,
+            )
+            .unwrap();
+
+        a_peer.write_all(b"x").unwrap();
+        b_peer.write_all(b"x").unwrap();
+
+        // Only one event fits in the buffer, so the two events are
+        // dispatched by separate iterations.
+        assert_eq!(1, fdevent.run_once(Some(TIMEOUT)).unwrap());
+        assert_eq!(1, fdevent.run_once(Some(TIMEOUT)).unwrap());
+        let mut dispatched = dispatched.borrow().clone();
+        dispatched.sort();
+        assert_eq!(vec![token_a, token_b], dispatched);
+    }
+
+    #[test]
+    fn tokens() {
+        let (a, _a_peer) = UnixStream::pair().unwrap();
+        let (b, _b_peer) = UnixStream::pair().unwrap();
+        let mut fdevent = Fdevent::new().unwrap();
+        assert_eq!(0, fdevent.tokens().count());
+
+        let token_a = fdevent.register(&a, |_| {}, Interest::READABLE).unwrap();
+        let token_b = fdevent.register(&b, |_| {}, Interest::WRITABLE).unwrap();
+        let mut tokens: Vec<_> = fdevent.tokens().collect();
+        tokens.sort();
+        assert_eq!(vec![token_a, token_b], tokens);
+        assert_eq!(Some(a.as_raw_fd()), fdevent.fd_for(token_a));
+        assert_eq!(Some(b.as_raw_fd()), fdevent.fd_for(token_b));
+
+        fdevent.unregister(&a, token_a).unwrap();
+        assert_eq!(vec![token_b], fdevent.tokens().collect::<Vec<_>>());
+        assert_eq!(None, fdevent.fd_for(token_a));
+    }
+}
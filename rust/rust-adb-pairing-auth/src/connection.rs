@@ -0,0 +1,229 @@
+use crate::{PairingAuthCtx, PairingAuthCtxBuilder, PairingAuthError, Role};
+use std::io::{Read, Write};
+
+/// The maximum size of the peer info exchanged during pairing.
+pub const MAX_PEER_INFO_SIZE: usize = 8192;
+
+const CURRENT_KEY_HEADER_VERSION: u8 = 1;
+const MIN_SUPPORTED_KEY_HEADER_VERSION: u8 = 1;
+const MAX_SUPPORTED_KEY_HEADER_VERSION: u8 = 1;
+pub(crate) const MAX_PAYLOAD_SIZE: usize = MAX_PEER_INFO_SIZE * 2;
+
+/// The size of the packet header: version (1 byte), type (1 byte) and
+/// payload size (4 bytes, big-endian).
+const HEADER_SIZE: usize = 6;
+
+/// The type of a pairing packet, from `PairingPacket.Type` in
+/// `original/proto/pairing.proto`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum PacketType {
+    Spake2Msg = 0,
+    PeerInfo = 1,
+}
+
+/// The progress of a `PairingConnection`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairingState {
+    /// The pairing hasn't started yet.
+    Ready,
+    /// The SPAKE2 messages are being exchanged.
+    ExchangingMsgs,
+    /// The encrypted peer info is being exchanged, which verifies that both
+    /// sides used the same password.
+    ExchangingPeerInfo,
+    /// The pairing succeeded.
+    Done,
+    /// The pairing failed.
+    Failed,
+}
+
+/// A callback invoked on every state transition of a `PairingConnection`.
+pub type StateHandler = Box<dyn FnMut(PairingState) + Send>;
+
+/// The pairing protocol between two peers over a byte stream. On success,
+/// both sides have each other's peer info.
+///
+/// This is a port of the C++ `PairingConnectionCtx` in
+/// `original/pairing_connection/pairing_connection.cpp`. Unlike the C++
+/// version, it doesn't set up TLS or spawn a worker thread: `start` runs the
+/// protocol to completion on whatever stream it's given, and the caller
+/// decides which thread it runs on.
+///
+/// Only peers using this crate can pair with each other. The SPAKE2 message
+/// is `SPAKE2_MSG_SIZE` bytes, while C++ peers, which use BoringSSL's SPAKE2,
+/// send 32-byte messages with a different transcript. Every real C++ peer is
+/// therefore rejected with `PeerMessageInvalid`.
+pub struct PairingConnection {
+    role: Role,
+    pswd: Vec<u8>,
+    peer_info: Vec<u8>,
+    state: PairingState,
+    on_state_change: Option<StateHandler>,
+}
+
+impl PairingConnection {
+    /// Creates a new `PairingConnection`.
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - The role of this participant.
+    /// * `pswd` - The shared password.
+    /// * `peer_info` - The info sent to the other party, at most
+    ///   `MAX_PEER_INFO_SIZE` bytes.
+    pub fn new(role: Role, pswd: &[u8], peer_info: &[u8]) -> Result<Self, PairingAuthError> {
+        if pswd.is_empty() {
+            return Err(PairingAuthError::PasswordEmpty);
+        }
+        if peer_info.len() > MAX_PEER_INFO_SIZE {
+            return Err(PairingAuthError::PeerInfoTooLarge);
+        }
+        Ok(Self {
+            role,
+            pswd: pswd.to_vec(),
+            peer_info: peer_info.to_vec(),
+            state: PairingState::Ready,
+            on_state_change: None,
+        })
+    }
+
+    /// Sets a callback invoked after every state transition, e.g. to show
+    /// the progress of the pairing in a UI.
+    pub fn on_state_change<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(PairingState) + Send + 'static,
+    {
+        self.on_state_change = Some(Box::new(handler));
+        self
+    }
+
+    /// Returns the current state of the pairing.
+    pub fn state(&self) -> PairingState {
+        self.state
+    }
+
+    /// Runs the pairing protocol over `stream`, returning the peer info of
+    /// the other party.
+    ///
+    /// This can only be called once in the lifetime of the connection.
+    pub fn start<S: Read + Write>(&mut self, stream: &mut S) -> Result<Vec<u8>, PairingAuthError> {
+        self.start_with_ctx(stream)
+            .map(|(_, their_info)| their_info)
+    }
+
+    /// Like `start`, but also returns the context of the established cipher.
+    fn start_with_ctx<S: Read + Write>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<(PairingAuthCtx, Vec<u8>), PairingAuthError> {
+        if self.state != PairingState::Ready {
+            return Err(PairingAuthError::AlreadyStarted);
+        }
+        match self.run(stream) {
+            Ok(result) => {
+                self.set_state(PairingState::Done);
+                Ok(result)
+            }
+            Err(e) => {
+                self.set_state(PairingState::Failed);
+                Err(e)
+            }
+        }
+    }
+
+    fn run<S: Read + Write>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<(PairingAuthCtx, Vec<u8>), PairingAuthError> {
+        self.set_state(PairingState::ExchangingMsgs);
+        let mut auth = self.exchange_msgs(stream)?;
+        self.set_state(PairingState::ExchangingPeerInfo);
+        let their_info = self.exchange_peer_info(stream, &mut auth)?;
+        Ok((auth, their_info))
+    }
+
+    fn exchange_msgs<S: Read + Write>(
+        &self,
+        stream: &mut S,
+    ) -> Result<PairingAuthCtx, PairingAuthError> {
+        let builder = PairingAuthCtxBuilder::new(&self.pswd, self.role)?;
+        write_packet(stream, PacketType::Spake2Msg, builder.msg())?;
+        let their_msg = read_packet(stream, PacketType::Spake2Msg)?;
+        builder.init_cipher(&their_msg)
+    }
+
+    fn exchange_peer_info<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        auth: &mut PairingAuthCtx,
+    ) -> Result<Vec<u8>, PairingAuthError> {
+        let encrypted = auth.encrypt(&self.peer_info)?;
+        write_packet(stream, PacketType::PeerInfo, &encrypted)?;
+        let their_encrypted = read_packet(stream, PacketType::PeerInfo)?;
+        let their_info = auth.decrypt(&their_encrypted)?;
+        if their_info.len() > MAX_PEER_INFO_SIZE {
+            return Err(PairingAuthError::PeerInfoTooLarge);
+        }
+        Ok(their_info)
+    }
+
+    fn set_state(&mut self, state: PairingState) {
+        self.state = state;
+        if let Some(handler) = &mut self.on_state_change {
+            handler(state);
+        }
+    }
+}
+
+/// Runs the whole pairing flow over `stream` as `role`: the SPAKE2 exchange
+/// with `password`, then the encrypted exchange of `my_pubkey` for the
+/// public key of the other party.
+///
+/// Returns the context of the established cipher, e.g. to exchange further
+/// encrypted messages, and the public key of the other party.
+///
+/// Like `PairingConnection`, this can't pair with the C++ implementation,
+/// such as `adb pair` or adbd: their SPAKE2 messages are rejected with
+/// `PeerMessageInvalid`.
+pub fn pair<S: Read + Write>(
+    stream: &mut S,
+    password: &[u8],
+    role: Role,
+    my_pubkey: &[u8],
+) -> Result<(PairingAuthCtx, Vec<u8>), PairingAuthError> {
+    PairingConnection::new(role, password, my_pubkey)?.start_with_ctx(stream)
+}
+
+fn write_packet<W: Write>(
+    writer: &mut W,
+    packet_type: PacketType,
+    payload: &[u8],
+) -> Result<(), PairingAuthError> {
+    let mut header = [0u8; HEADER_SIZE];
+    header[0] = CURRENT_KEY_HEADER_VERSION;
+    header[1] = packet_type as u8;
+    header[2..].copy_from_slice(&(payload.len() as u32).to_be_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_packet<R: Read>(reader: &mut R, expected: PacketType) -> Result<Vec<u8>, PairingAuthError> {
+    let mut header = [0u8; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    let version = header[0];
+    if !(MIN_SUPPORTED_KEY_HEADER_VERSION..=MAX_SUPPORTED_KEY_HEADER_VERSION).contains(&version) {
+        return Err(PairingAuthError::InvalidHeader);
+    }
+    if header[1] != expected as u8 {
+        return Err(PairingAuthError::InvalidHeader);
+    }
+    let size = u32::from_be_bytes(header[2..].try_into().unwrap()) as usize;
+    if size == 0 || size > MAX_PAYLOAD_SIZE {
+        return Err(PairingAuthError::InvalidHeader);
+    }
+    let mut payload = vec![0u8; size];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
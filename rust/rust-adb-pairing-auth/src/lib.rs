@@ -5,11 +5,26 @@
 //! in `original/pairing_auth`.
 
 pub mod aes_128_gcm;
+mod connection;
+
+pub use self::connection::{
+    pair, PairingConnection, PairingState, StateHandler, MAX_PEER_INFO_SIZE,
+};
 
 use self::aes_128_gcm::{Aes128GcmCipher, Aes128GcmError};
+use rand_chacha::ChaCha20Rng;
+use rand_core::{OsRng, RngCore, SeedableRng};
 use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::io::{self, Read, Write};
 use thiserror::Error;
 
+/// The size of a SPAKE2 message for `Ed25519Group`: a byte identifying the
+/// side of the sender, followed by the 32-byte group element.
+///
+/// This differs from the 32-byte messages of BoringSSL's SPAKE2, used by
+/// the C++ implementation, which this crate can't pair with.
+pub const SPAKE2_MSG_SIZE: usize = 1 + 32;
+
 const CLIENT_NAME: &[u8] = b"adb pair client";
 const SERVER_NAME: &[u8] = b"adb pair server";
 
@@ -25,6 +40,23 @@ pub enum PairingAuthError {
     /// The password was empty.
     #[error("Password cannot be empty")]
     PasswordEmpty,
+    /// An I/O error occurred while exchanging pairing packets.
+    #[error("I/O error")]
+    Io(#[from] io::Error),
+    /// A pairing packet had an unsupported version, an unexpected type or an
+    /// invalid payload size.
+    #[error("Invalid pairing packet header")]
+    InvalidHeader,
+    /// The peer info exceeded `MAX_PEER_INFO_SIZE`.
+    #[error("Peer info is too large")]
+    PeerInfoTooLarge,
+    /// The message received from the other party is not a SPAKE2 message of
+    /// `SPAKE2_MSG_SIZE` bytes.
+    #[error("Invalid peer message of {0} bytes")]
+    PeerMessageInvalid(usize),
+    /// The pairing connection was started more than once.
+    #[error("Pairing already started")]
+    AlreadyStarted,
 }
 
 impl From<spake2::Error> for PairingAuthError {
@@ -43,11 +75,16 @@ impl From<spake2::Error> for PairingAuthError {
 /// safety. This ensures that the `PairingAuthCtx` can only be created after the
 /// cipher has been successfully initialized.
 pub struct PairingAuthCtxBuilder {
-    state: Spake2<Ed25519Group>,
+    pswd: Vec<u8>,
+    role: Role,
+    /// The seed of the random scalar of our SPAKE2 state, so that the state
+    /// can be recreated for every `try_init_cipher` with the same message.
+    seed: [u8; 32],
     our_msg: Vec<u8>,
 }
 
 /// The role of the pairing participant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Role {
     /// The client role.
     Client,
@@ -67,19 +104,34 @@ impl PairingAuthCtxBuilder {
             return Err(PairingAuthError::PasswordEmpty);
         }
 
-        let password = Password::new(pswd);
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        let mut builder = Self {
+            pswd: pswd.to_vec(),
+            role,
+            seed,
+            our_msg: Vec::new(),
+        };
+        builder.our_msg = builder.start().1;
+        Ok(builder)
+    }
+
+    /// Starts the SPAKE2 exchange, which always gives the same state and
+    /// message for the same builder.
+    fn start(&self) -> (Spake2<Ed25519Group>, Vec<u8>) {
+        let password = Password::new(&self.pswd);
         let client_id = Identity::new(CLIENT_NAME);
         let server_id = Identity::new(SERVER_NAME);
+        let rng = ChaCha20Rng::from_seed(self.seed);
 
-        let (state, our_msg) = match role {
-            Role::Client => Spake2::<Ed25519Group>::start_a(&password, &client_id, &server_id),
-            Role::Server => Spake2::<Ed25519Group>::start_b(&password, &client_id, &server_id),
-        };
-
-        Ok(Self {
-            state,
-            our_msg: our_msg.to_vec(),
-        })
+        match self.role {
+            Role::Client => {
+                Spake2::<Ed25519Group>::start_a_with_rng(&password, &client_id, &server_id, rng)
+            }
+            Role::Server => {
+                Spake2::<Ed25519Group>::start_b_with_rng(&password, &client_id, &server_id, rng)
+            }
+        }
     }
 
     /// Returns the message to be sent to the other party.
@@ -92,18 +144,55 @@ impl PairingAuthCtxBuilder {
     ///
     /// # Arguments
     ///
-    /// * `their_msg` - The message received from the other party.
+    /// * `their_msg` - The message received from the other party, which must
+    ///   be `SPAKE2_MSG_SIZE` bytes.
     pub fn init_cipher(self, their_msg: &[u8]) -> Result<PairingAuthCtx, PairingAuthError> {
-        let key_material = self.state.finish(their_msg)?;
+        self.try_init_cipher(their_msg)
+    }
+
+    /// Initializes the cipher like `init_cipher`, but leaves the builder
+    /// usable, so that the handshake can be retried with another message
+    /// from the other party if this one is rejected. Our message stays the
+    /// same across retries, so the other party doesn't need to receive it
+    /// again.
+    ///
+    /// Reusing our message means reusing the same secret SPAKE2 scalar. Each
+    /// attempt with a message of an attacker's choosing gives them another
+    /// online guess of the password, so callers must bound the number of
+    /// retries, e.g. to the handful allowed for a user typing a pairing code.
+    pub fn try_init_cipher(&self, their_msg: &[u8]) -> Result<PairingAuthCtx, PairingAuthError> {
+        if their_msg.len() != SPAKE2_MSG_SIZE {
+            return Err(PairingAuthError::PeerMessageInvalid(their_msg.len()));
+        }
+        let (state, _) = self.start();
+        let key_material = state.finish(their_msg)?;
         let cipher = Aes128GcmCipher::new(&key_material)?;
-        Ok(PairingAuthCtx { cipher })
+        Ok(PairingAuthCtx {
+            cipher,
+            stats: PairingStats::default(),
+        })
     }
 }
 
+/// Counters of the data that went through a `PairingAuthCtx`, e.g. for
+/// metrics. Only successful operations are counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PairingStats {
+    /// The number of messages encrypted.
+    pub messages_encrypted: u64,
+    /// The number of messages decrypted.
+    pub messages_decrypted: u64,
+    /// The total size of the plaintext of the encrypted messages.
+    pub bytes_encrypted: u64,
+    /// The total size of the plaintext of the decrypted messages.
+    pub bytes_decrypted: u64,
+}
+
 /// A pairing authentication context. This is used to encrypt and decrypt
 /// messages after the cipher has been initialized.
 pub struct PairingAuthCtx {
     cipher: Aes128GcmCipher,
+    stats: PairingStats,
 }
 
 impl PairingAuthCtx {
@@ -113,7 +202,10 @@ impl PairingAuthCtx {
     ///
     /// * `data` - The data to encrypt.
     pub fn encrypt(&mut self, data: &[u8]) -> Result<Vec<u8>, PairingAuthError> {
-        Ok(self.cipher.encrypt(data)?)
+        let encrypted = self.cipher.encrypt(data)?;
+        self.stats.messages_encrypted += 1;
+        self.stats.bytes_encrypted += data.len() as u64;
+        Ok(encrypted)
     }
 
     /// Decrypts the given data.
@@ -122,6 +214,45 @@ impl PairingAuthCtx {
     ///
     /// * `data` - The data to decrypt.
     pub fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>, PairingAuthError> {
-        Ok(self.cipher.decrypt(data)?)
+        let decrypted = self.cipher.decrypt(data)?;
+        self.stats.messages_decrypted += 1;
+        self.stats.bytes_decrypted += decrypted.len() as u64;
+        Ok(decrypted)
+    }
+
+    /// Returns the amount of data encrypted and decrypted so far.
+    pub fn stats(&self) -> PairingStats {
+        self.stats
+    }
+
+    /// Encrypts `plaintext` and writes it to `writer`, prefixed with the
+    /// length of the ciphertext as a 4-byte big-endian integer.
+    pub fn send_encrypted<W: Write>(
+        &mut self,
+        writer: &mut W,
+        plaintext: &[u8],
+    ) -> Result<(), PairingAuthError> {
+        let encrypted = self.encrypt(plaintext)?;
+        writer.write_all(&(encrypted.len() as u32).to_be_bytes())?;
+        writer.write_all(&encrypted)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a message written by `send_encrypted` from `reader`, and
+    /// decrypts it.
+    ///
+    /// Messages larger than twice `MAX_PEER_INFO_SIZE` are rejected with
+    /// `InvalidHeader` before they're read.
+    pub fn recv_encrypted<R: Read>(&mut self, reader: &mut R) -> Result<Vec<u8>, PairingAuthError> {
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > connection::MAX_PAYLOAD_SIZE {
+            return Err(PairingAuthError::InvalidHeader);
+        }
+        let mut encrypted = vec![0u8; len];
+        reader.read_exact(&mut encrypted)?;
+        self.decrypt(&encrypted)
     }
 }
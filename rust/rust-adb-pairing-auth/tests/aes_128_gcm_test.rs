@@ -1,5 +1,25 @@
 use rust_adb_pairing_auth::aes_128_gcm::{Aes128GcmCipher, Aes128GcmError};
 
+// Known answers for the cipher, produced by an independent implementation of
+// the same construction (HKDF-SHA256 with the same info string, and
+// AES-128-GCM with the little-endian sequence number as the nonce). They
+// weren't captured from the C++ implementation. Each ciphertext in the file
+// is prefixed with its length as a 4-byte big-endian integer.
+const KNOWN_KEY_MATERIAL: &[u8] = include_bytes!("testdata/aes_128_gcm_key_material.bin");
+const KNOWN_CIPHERTEXTS: &[u8] = include_bytes!("testdata/aes_128_gcm_ciphertexts.bin");
+const KNOWN_PLAINTEXTS: [&[u8]; 2] = [b"adb pairing peer info", b"second message"];
+
+fn known_ciphertexts() -> Vec<&'static [u8]> {
+    let mut result = Vec::new();
+    let mut rest = KNOWN_CIPHERTEXTS;
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        result.push(&rest[4..4 + len]);
+        rest = &rest[4 + len..];
+    }
+    result
+}
+
 #[test]
 fn aes_128_gcm_init_empty_material() {
     let material = &[];
@@ -20,3 +40,21 @@ fn aes_128_gcm_encrypt_decrypt() {
 
     assert_eq!(msg.to_vec(), decrypted);
 }
+
+#[test]
+fn aes_128_gcm_decrypt_known_answers() {
+    let mut cipher = Aes128GcmCipher::new(KNOWN_KEY_MATERIAL).unwrap();
+    let ciphertexts = known_ciphertexts();
+    assert_eq!(KNOWN_PLAINTEXTS.len(), ciphertexts.len());
+    for (plaintext, ciphertext) in KNOWN_PLAINTEXTS.iter().zip(ciphertexts) {
+        assert_eq!(plaintext.to_vec(), cipher.decrypt(ciphertext).unwrap());
+    }
+}
+
+#[test]
+fn aes_128_gcm_encrypt_known_answers() {
+    let mut cipher = Aes128GcmCipher::new(KNOWN_KEY_MATERIAL).unwrap();
+    for (plaintext, ciphertext) in KNOWN_PLAINTEXTS.iter().zip(known_ciphertexts()) {
+        assert_eq!(ciphertext.to_vec(), cipher.encrypt(plaintext).unwrap());
+    }
+}
@@ -0,0 +1,60 @@
+use rust_adb_pairing_auth::{pair, PairingAuthError, Role};
+use std::thread;
+
+#[test]
+fn pair_exchanges_pubkeys() {
+    let (mut client_stream, mut server_stream) = sysdeps::socketpair().unwrap();
+    let server = thread::spawn(move || {
+        let (mut ctx, client_pubkey) = pair(
+            &mut server_stream,
+            b"123456",
+            Role::Server,
+            b"server pubkey",
+        )
+        .unwrap();
+        assert_eq!(b"client pubkey", client_pubkey.as_slice());
+        let msg = ctx.recv_encrypted(&mut server_stream).unwrap();
+        ctx.send_encrypted(&mut server_stream, &msg).unwrap();
+    });
+
+    let (mut ctx, server_pubkey) = pair(
+        &mut client_stream,
+        b"123456",
+        Role::Client,
+        b"client pubkey",
+    )
+    .unwrap();
+    assert_eq!(b"server pubkey", server_pubkey.as_slice());
+
+    // Both sides ended up with the same cipher.
+    ctx.send_encrypted(&mut client_stream, b"echo").unwrap();
+    assert_eq!(
+        b"echo",
+        ctx.recv_encrypted(&mut client_stream).unwrap().as_slice()
+    );
+    server.join().unwrap();
+}
+
+#[test]
+fn pair_wrong_password() {
+    let (mut client_stream, mut server_stream) = sysdeps::socketpair().unwrap();
+    let server = thread::spawn(move || {
+        pair(
+            &mut server_stream,
+            b"123456",
+            Role::Server,
+            b"server pubkey",
+        )
+        .map(|_| ())
+    });
+
+    let result = pair(
+        &mut client_stream,
+        b"654321",
+        Role::Client,
+        b"client pubkey",
+    );
+    assert!(matches!(result, Err(PairingAuthError::CipherError(_))));
+    drop(client_stream);
+    assert!(server.join().unwrap().is_err());
+}
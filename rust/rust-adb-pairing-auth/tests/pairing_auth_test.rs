@@ -1,5 +1,7 @@
 use rust_adb_pairing_auth::aes_128_gcm::Aes128GcmError;
-use rust_adb_pairing_auth::{PairingAuthCtxBuilder, PairingAuthError, Role};
+use rust_adb_pairing_auth::{
+    PairingAuthCtxBuilder, PairingAuthError, PairingStats, Role, SPAKE2_MSG_SIZE,
+};
 
 #[test]
 fn pairing_auth_empty_password() {
@@ -110,3 +112,133 @@ fn pairing_auth_corrupted_payload() {
         ))
     ));
 }
+
+#[test]
+fn pairing_auth_encrypted_stream() {
+    let pswd = b"password";
+    let client_builder = PairingAuthCtxBuilder::new(pswd, Role::Client).unwrap();
+    let client_msg = client_builder.msg().to_vec();
+    let server_builder = PairingAuthCtxBuilder::new(pswd, Role::Server).unwrap();
+    let server_msg = server_builder.msg().to_vec();
+    let mut client = client_builder.init_cipher(&server_msg).unwrap();
+    let mut server = server_builder.init_cipher(&client_msg).unwrap();
+
+    // Two messages written back to back, and read from the same stream.
+    let mut stream = Vec::new();
+    client.send_encrypted(&mut stream, b"peer info").unwrap();
+    client.send_encrypted(&mut stream, b"").unwrap();
+    let len = u32::from_be_bytes(stream[..4].try_into().unwrap()) as usize;
+    assert_eq!(client.encrypt(b"peer info").unwrap().len(), len);
+
+    let mut reader = stream.as_slice();
+    assert_eq!(b"peer info".to_vec(), server.recv_encrypted(&mut reader).unwrap());
+    assert_eq!(Vec::<u8>::new(), server.recv_encrypted(&mut reader).unwrap());
+    assert!(matches!(
+        server.recv_encrypted(&mut reader),
+        Err(PairingAuthError::Io(_))
+    ));
+
+    // An oversized length prefix is rejected without reading the payload.
+    let oversized = u32::MAX.to_be_bytes();
+    assert!(matches!(
+        server.recv_encrypted(&mut oversized.as_slice()),
+        Err(PairingAuthError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn pairing_auth_peer_message_size() {
+    let pswd = b"password";
+    let server_msg = PairingAuthCtxBuilder::new(pswd, Role::Server)
+        .unwrap()
+        .msg()
+        .to_vec();
+    assert_eq!(SPAKE2_MSG_SIZE, server_msg.len());
+
+    // Undersized
+    let client = PairingAuthCtxBuilder::new(pswd, Role::Client).unwrap();
+    let result = client.init_cipher(&server_msg[..SPAKE2_MSG_SIZE - 1]);
+    assert!(matches!(result, Err(PairingAuthError::PeerMessageInvalid(32))));
+
+    // Oversized
+    let client = PairingAuthCtxBuilder::new(pswd, Role::Client).unwrap();
+    let oversized = vec![0x42; 1024 * 1024];
+    let result = client.init_cipher(&oversized);
+    assert!(matches!(
+        result,
+        Err(PairingAuthError::PeerMessageInvalid(1048576))
+    ));
+
+    // Empty
+    let client = PairingAuthCtxBuilder::new(pswd, Role::Client).unwrap();
+    let result = client.init_cipher(&[]);
+    assert!(matches!(result, Err(PairingAuthError::PeerMessageInvalid(0))));
+
+    let client = PairingAuthCtxBuilder::new(pswd, Role::Client).unwrap();
+    assert!(client.init_cipher(&server_msg).is_ok());
+}
+
+#[test]
+fn pairing_auth_retry_init_cipher() {
+    let pswd = b"password";
+    let client_builder = PairingAuthCtxBuilder::new(pswd, Role::Client).unwrap();
+    let client_msg = client_builder.msg().to_vec();
+    let server_builder = PairingAuthCtxBuilder::new(pswd, Role::Server).unwrap();
+    let server_msg = server_builder.msg().to_vec();
+
+    // A message claiming to be from the client side is rejected by the
+    // client.
+    let mut bad_msg = server_msg.clone();
+    bad_msg[0] = client_msg[0];
+    assert!(matches!(
+        client_builder.try_init_cipher(&bad_msg),
+        Err(PairingAuthError::Spake2Error)
+    ));
+
+    // The retry uses the same client message, which the server already has.
+    assert_eq!(client_msg, client_builder.msg());
+    let mut client = client_builder.try_init_cipher(&server_msg).unwrap();
+    let mut server = server_builder.init_cipher(&client_msg).unwrap();
+
+    let msg = b"hello";
+    let encrypted = client.encrypt(msg).unwrap();
+    assert_eq!(msg.to_vec(), server.decrypt(&encrypted).unwrap());
+}
+
+#[test]
+fn pairing_auth_stats() {
+    let pswd = b"password";
+    let client_builder = PairingAuthCtxBuilder::new(pswd, Role::Client).unwrap();
+    let server_builder = PairingAuthCtxBuilder::new(pswd, Role::Server).unwrap();
+    let mut client = client_builder.try_init_cipher(server_builder.msg()).unwrap();
+    let mut server = server_builder.init_cipher(client_builder.msg()).unwrap();
+    assert_eq!(PairingStats::default(), client.stats());
+
+    for msg in [&b"hello"[..], &[0u8; 100], b""] {
+        let encrypted = client.encrypt(msg).unwrap();
+        server.decrypt(&encrypted).unwrap();
+    }
+    let reply = server.encrypt(b"world").unwrap();
+    client.decrypt(&reply).unwrap();
+
+    let expected = PairingStats {
+        messages_encrypted: 3,
+        messages_decrypted: 1,
+        bytes_encrypted: 105,
+        bytes_decrypted: 5,
+    };
+    assert_eq!(expected, client.stats());
+    assert_eq!(
+        PairingStats {
+            messages_encrypted: 1,
+            messages_decrypted: 3,
+            bytes_encrypted: 5,
+            bytes_decrypted: 105,
+        },
+        server.stats()
+    );
+
+    // A failed decryption isn't counted.
+    assert!(client.decrypt(&[0u8; 32]).is_err());
+    assert_eq!(expected, client.stats());
+}
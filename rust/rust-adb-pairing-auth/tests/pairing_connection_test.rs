@@ -0,0 +1,71 @@
+use rust_adb_pairing_auth::{PairingAuthError, PairingConnection, PairingState, Role};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+
+/// Runs a pairing between a client and a server over a loopback socket,
+/// returning the result and the observed states of the client.
+fn pair(
+    client_pswd: &'static [u8],
+    server_pswd: &'static [u8],
+) -> (Result<Vec<u8>, PairingAuthError>, Vec<PairingState>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut conn = PairingConnection::new(Role::Server, server_pswd, b"server info").unwrap();
+        conn.start(&mut stream)
+    });
+
+    let (tx, rx) = mpsc::channel();
+    let mut conn = PairingConnection::new(Role::Client, client_pswd, b"client info")
+        .unwrap()
+        .on_state_change(move |state| tx.send(state).unwrap());
+    assert_eq!(PairingState::Ready, conn.state());
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let result = conn.start(&mut stream);
+
+    let server_result = server.join().unwrap();
+    if let Ok(info) = &result {
+        assert_eq!(b"client info", server_result.unwrap().as_slice());
+        assert_eq!(b"server info", info.as_slice());
+    }
+    let states: Vec<_> = rx.try_iter().collect();
+    assert_eq!(Some(&conn.state()), states.last());
+    (result, states)
+}
+
+#[test]
+fn pairing_connection_states() {
+    let (result, states) = pair(b"password", b"password");
+    assert!(result.is_ok());
+    assert_eq!(
+        vec![
+            PairingState::ExchangingMsgs,
+            PairingState::ExchangingPeerInfo,
+            PairingState::Done,
+        ],
+        states
+    );
+}
+
+#[test]
+fn pairing_connection_wrong_password() {
+    let (result, states) = pair(b"password", b"passw0rd");
+    assert!(result.is_err());
+    assert_eq!(
+        vec![
+            PairingState::ExchangingMsgs,
+            PairingState::ExchangingPeerInfo,
+            PairingState::Failed,
+        ],
+        states
+    );
+}
+
+#[test]
+fn pairing_connection_peer_info_too_large() {
+    let info = vec![0; rust_adb_pairing_auth::MAX_PEER_INFO_SIZE + 1];
+    let result = PairingConnection::new(Role::Client, b"password", &info);
+    assert!(matches!(result, Err(PairingAuthError::PeerInfoTooLarge)));
+}
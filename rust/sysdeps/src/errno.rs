@@ -0,0 +1,103 @@
+/// The host errno values and their wire values, which are the Linux
+/// asm-generic values used on all Android architectures.
+const ERRNO_VALUES: &[(i32, i32)] = &[
+    (libc::EACCES, 13),
+    (libc::EEXIST, 17),
+    (libc::EFAULT, 14),
+    (libc::EFBIG, 27),
+    (libc::EINTR, 4),
+    (libc::EINVAL, 22),
+    (libc::EIO, 5),
+    (libc::EISDIR, 21),
+    (libc::ELOOP, 40),
+    (libc::EMFILE, 24),
+    (libc::ENAMETOOLONG, 36),
+    (libc::ENFILE, 23),
+    (libc::ENOENT, 2),
+    (libc::ENOMEM, 12),
+    (libc::ENOSPC, 28),
+    (libc::ENOTDIR, 20),
+    (libc::EOVERFLOW, 75),
+    (libc::EPERM, 1),
+    (libc::EROFS, 30),
+    (libc::ETXTBSY, 26),
+    // `EWOULDBLOCK` is the same as `EAGAIN` on every supported host.
+    (libc::EAGAIN, 11),
+    (libc::EPIPE, 32),
+    (libc::ECONNRESET, 104),
+    (libc::ECONNREFUSED, 111),
+    (libc::ETIMEDOUT, 110),
+    (libc::EADDRINUSE, 98),
+    (libc::ENETUNREACH, 101),
+    (libc::EHOSTUNREACH, 113),
+];
+
+/// The wire value of `EIO`, which unknown errnos are reported as.
+const WIRE_EIO: i32 = 5;
+
+/// Converts a host errno to its value on the wire, e.g. in a sync `FAIL`
+/// response. Unknown errnos are converted to `EIO`.
+///
+/// This is a port of `errno_to_wire` in `original/sysdeps/errno.cpp`.
+pub fn errno_to_wire(error: i32) -> i32 {
+    ERRNO_VALUES
+        .iter()
+        .find(|(host, _)| *host == error)
+        .map_or(WIRE_EIO, |(_, wire)| *wire)
+}
+
+/// Converts an errno received on the wire to the host value. Unknown errnos
+/// are converted to `EIO`.
+///
+/// This is a port of `errno_from_wire` in `original/sysdeps/errno.cpp`.
+/// Unlike the C++ version, which looks the value up in the host-to-wire
+/// table, this uses the wire values, so it also works on hosts whose errno
+/// values differ from Linux.
+pub fn errno_from_wire(error: i32) -> i32 {
+    ERRNO_VALUES
+        .iter()
+        .find(|(_, wire)| *wire == error)
+        .map_or(libc::EIO, |(host, _)| *host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn wire_values_match_linux() {
+        for (host, wire) in ERRNO_VALUES {
+            assert_eq!(host, wire);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for error in [
+            libc::EAGAIN,
+            libc::EWOULDBLOCK,
+            libc::EPIPE,
+            libc::ECONNRESET,
+            libc::ECONNREFUSED,
+            libc::ETIMEDOUT,
+            libc::EADDRINUSE,
+            libc::ENETUNREACH,
+            libc::EHOSTUNREACH,
+        ] {
+            assert_ne!(WIRE_EIO, errno_to_wire(error), "{}", error);
+            assert_eq!(error, errno_from_wire(errno_to_wire(error)), "{}", error);
+        }
+        for (host, _) in ERRNO_VALUES {
+            assert_eq!(*host, errno_from_wire(errno_to_wire(*host)));
+        }
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(WIRE_EIO, errno_to_wire(libc::EDOM));
+        assert_eq!(WIRE_EIO, errno_to_wire(-1));
+        assert_eq!(libc::EIO, errno_from_wire(-1));
+        assert_eq!(libc::EIO, errno_from_wire(0));
+    }
+}
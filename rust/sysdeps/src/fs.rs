@@ -0,0 +1,373 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Creates a directory at `path` with exactly the permissions in `mode`.
+///
+/// Unlike the C++ `adb_mkdir`, the process umask is not applied: the mode is
+/// explicitly set after the directory is created. The directory is created
+/// with the group and other bits cleared, so it's never more accessible than
+/// `mode` in between. On Windows, `mode` is ignored.
+///
+/// Like `std::fs::create_dir`, this fails if the directory already exists.
+pub fn create_dir_mode(path: &Path, mode: u32) -> io::Result<()> {
+    create_private_dir(path, mode)?;
+    set_mode(path, mode)
+}
+
+#[cfg(unix)]
+fn create_private_dir(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(mode & 0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path, _mode: u32) -> io::Result<()> {
+    fs::create_dir(path)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Opens the file at `path` with `options`, like the C++ `adb_open`.
+///
+/// The file is always opened with close-on-exec on Unix, so that it isn't
+/// leaked into the processes spawned by shell services. On Windows, the file
+/// is always in binary mode: `File` is a raw handle with no CRLF
+/// translation, so file transfers are never corrupted.
+pub fn open_file(path: &Path, options: OpenOptions) -> io::Result<File> {
+    #[cfg(unix)]
+    let options = {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut options = options;
+        options.custom_flags(libc::O_CLOEXEC);
+        options
+    };
+    options.open(path)
+}
+
+/// Creates a new, uniquely named file in `dir` whose name starts with
+/// `prefix`, returning the open file and its path, like `mkstemp`.
+///
+/// The file is created exclusively, so an existing file is never reused, and
+/// is only accessible by the current user (mode 0600) on Unix. The caller is
+/// responsible for removing or renaming the file.
+pub fn mkstemp(dir: &Path, prefix: &str) -> io::Result<(File, PathBuf)> {
+    const ATTEMPTS: usize = 100;
+    for _ in 0..ATTEMPTS {
+        let path = dir.join(format!("{}{:016x}", prefix, random_suffix()));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match open_file(&path, options) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("failed to create a unique file in {}", dir.display()),
+    ))
+}
+
+/// Returns a value unpredictable enough for a temporary file name, from the
+/// randomly keyed std hasher.
+fn random_suffix() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+/// Removes the directory at `path` and everything in it, like
+/// `std::fs::remove_dir_all`, but more defensively.
+///
+/// This refuses to remove a filesystem root or the user's home directory,
+/// failing with `InvalidInput`, in case a bad path makes it to cleanup code.
+/// Symbolic links, including `path` itself, are removed rather than
+/// followed. On Windows, read-only
+/// files are made writable so they can be removed, and removals failing with
+/// a sharing violation, e.g. because a virus scanner briefly opened a file,
+/// are retried.
+pub fn remove_dir_all_safe(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return remove_with_retry(path, |path| fs::remove_file(path));
+    }
+    check_removable(path)?;
+    remove_tree(path)
+}
+
+fn check_removable(path: &Path) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let home = home_dir().and_then(|home| fs::canonicalize(home).ok());
+    if path.parent().is_none() || Some(&path) == home.as_ref() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("refusing to remove {}", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var)
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+fn remove_tree(path: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let path = entry.path();
+        // file_type doesn't follow symbolic links.
+        if entry.file_type()?.is_dir() {
+            remove_tree(&path)?;
+        } else {
+            remove_with_retry(&path, |path| fs::remove_file(path))?;
+        }
+    }
+    remove_with_retry(path, |path| fs::remove_dir(path))
+}
+
+#[cfg(not(windows))]
+fn remove_with_retry(path: &Path, remove: impl Fn(&Path) -> io::Result<()>) -> io::Result<()> {
+    remove(path)
+}
+
+#[cfg(windows)]
+fn remove_with_retry(path: &Path, remove: impl Fn(&Path) -> io::Result<()>) -> io::Result<()> {
+    use std::thread;
+    use std::time::Duration;
+    use windows_sys::Win32::Foundation::{ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION};
+
+    const ATTEMPTS: u32 = 10;
+    let mut cleared_readonly = false;
+    let mut attempt = 1;
+    loop {
+        let err = match remove(path) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        if err.kind() == io::ErrorKind::PermissionDenied && !cleared_readonly {
+            cleared_readonly = true;
+            let mut permissions = fs::symlink_metadata(path)?.permissions();
+            if permissions.readonly() {
+                #[allow(clippy::permissions_set_readonly_false)]
+                permissions.set_readonly(false);
+                fs::set_permissions(path, permissions)?;
+                continue;
+            }
+        }
+        let transient = [ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION]
+            .iter()
+            .any(|&code| err.raw_os_error() == Some(code as i32));
+        if !transient || attempt == ATTEMPTS {
+            return Err(err);
+        }
+        thread::sleep(Duration::from_millis(10 * u64::from(attempt)));
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("sysdeps-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn create_dir_mode_already_exists() {
+        let dir = test_dir("create_dir_mode_already_exists");
+        create_dir_mode(&dir, 0o755).unwrap();
+        let err = create_dir_mode(&dir, 0o755).unwrap_err();
+        assert_eq!(io::ErrorKind::AlreadyExists, err.kind());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn create_dir_mode_ignores_umask() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("create_dir_mode_ignores_umask");
+        // SAFETY: umask has no memory safety requirements.
+        let old_umask = unsafe { libc::umask(0) };
+        let result = create_dir_mode(&dir, 0o700);
+        unsafe { libc::umask(old_umask) };
+        result.unwrap();
+
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(0o700, mode & 0o777);
+        fs::remove_dir(&dir).unwrap();
+
+        // Group and other bits are only added after the directory is
+        // created, but still end up set.
+        create_dir_mode(&dir, 0o751).unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(0o751, mode & 0o777);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_file_binary() {
+        use std::io::{Read, Write};
+
+        let dir = test_dir("open_file_binary");
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("file");
+        let data = b"line\r\nline\n\r\x1a\0end";
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        open_file(&path, options).unwrap().write_all(data).unwrap();
+
+        let mut options = OpenOptions::new();
+        options.read(true);
+        let mut file = open_file(&path, options).unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(data.as_slice(), read.as_slice());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            // SAFETY: fcntl has no memory safety requirements.
+            let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFD) };
+            assert_ne!(0, flags & libc::FD_CLOEXEC);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mkstemp() {
+        use std::io::{Read, Seek, Write};
+
+        let dir = test_dir("mkstemp");
+        fs::create_dir(&dir).unwrap();
+        let (mut file, path) = super::mkstemp(&dir, "tmp-").unwrap();
+        let (_, other) = super::mkstemp(&dir, "tmp-").unwrap();
+        assert_ne!(path, other);
+        assert_eq!(Some(dir.as_path()), path.parent());
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("tmp-"));
+
+        file.write_all(b"data").unwrap();
+        file.rewind().unwrap();
+        let mut read = String::new();
+        file.read_to_string(&mut read).unwrap();
+        assert_eq!("data", read);
+        assert_eq!(b"data", fs::read(&path).unwrap().as_slice());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_dir_all_safe_read_only() {
+        let dir = test_dir("remove_dir_all_safe_read_only");
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("a/file"), b"foo").unwrap();
+        let read_only = dir.join("a/b/read_only");
+        fs::write(&read_only, b"bar").unwrap();
+        let mut permissions = fs::metadata(&read_only).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&read_only, permissions).unwrap();
+
+        remove_dir_all_safe(&dir).unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_dir_all_safe_symlink() {
+        let dir = test_dir("remove_dir_all_safe_symlink");
+        let target = test_dir("remove_dir_all_safe_symlink_target");
+        fs::create_dir(&dir).unwrap();
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("file"), b"foo").unwrap();
+        std::os::unix::fs::symlink(&target, dir.join("link")).unwrap();
+
+        remove_dir_all_safe(&dir).unwrap();
+        assert!(!dir.exists());
+        // The link is removed, not what it points to.
+        assert!(target.join("file").exists());
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_dir_all_safe_top_level_symlink() {
+        let link = test_dir("remove_dir_all_safe_top_level_symlink");
+        let target = test_dir("remove_dir_all_safe_top_level_symlink_target");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("file"), b"foo").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        remove_dir_all_safe(&link).unwrap();
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert!(target.join("file").exists());
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn check_removable() {
+        // Only the check is tested, so that a bug can't remove anything.
+        let root = std::env::current_dir()
+            .unwrap()
+            .ancestors()
+            .last()
+            .unwrap()
+            .to_path_buf();
+        let err = super::check_removable(&root).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        if let Some(home) = home_dir().filter(|home| home.exists()) {
+            let err = super::check_removable(&home).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        }
+
+        let dir = test_dir("check_removable");
+        assert_eq!(
+            io::ErrorKind::NotFound,
+            super::check_removable(&dir).unwrap_err().kind()
+        );
+        fs::create_dir(&dir).unwrap();
+        super::check_removable(&dir).unwrap();
+        // A path that only resolves to the root is rejected too.
+        let up = dir.ancestors().fold(dir.clone(), |path, _| path.join(".."));
+        let err = super::check_removable(&up).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        fs::remove_dir(&dir).unwrap();
+    }
+}
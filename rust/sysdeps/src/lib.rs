@@ -0,0 +1,33 @@
+//! A Rust port of the platform abstractions in `original/sysdeps.h`.
+//!
+//! Most of the C++ `adb_*` wrappers (`adb_read`, `adb_write`, `adb_close`,
+//! ...) are unnecessary in Rust, since the standard library already provides
+//! portable equivalents. This crate only contains the functionality that
+//! isn't covered by `std`.
+
+#[cfg(unix)]
+mod errno;
+mod fs;
+mod net;
+mod notify;
+mod parallelism;
+mod process;
+mod runtime;
+mod terminal;
+mod time;
+
+#[cfg(unix)]
+pub use errno::{errno_from_wire, errno_to_wire};
+pub use fs::{create_dir_mode, mkstemp, open_file, remove_dir_all_safe};
+pub use net::{
+    connect_timeout, disable_tcp_nagle, local_addr, local_ip_addresses, network_connect_timeout,
+    network_loopback_client, network_loopback_client_v6, network_loopback_server_dual,
+    network_loopback_server_ephemeral, network_loopback_server_v6, peer_addr, set_blocking,
+    set_socket_buffers, set_tcp_keepalive, shutdown, socketpair, LocalStream,
+};
+pub use notify::Notifier;
+pub use parallelism::available_parallelism;
+pub use process::{process_exists, terminate_process};
+pub use runtime::runtime_socket_path;
+pub use terminal::{is_terminal, terminal_size};
+pub use time::{unix_millis, wall_time};
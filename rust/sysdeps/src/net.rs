@@ -0,0 +1,666 @@
+use std::io;
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs,
+};
+use std::time::{Duration, Instant};
+
+/// Connects to `addr`, failing with `TimedOut` if the connection isn't
+/// established within `timeout`.
+///
+/// Unlike `TcpStream::connect`, this doesn't block for the full OS connect
+/// timeout when the peer is unreachable, e.g. a device that went away. The
+/// returned stream is in blocking mode. A zero `timeout` is rejected with
+/// `InvalidInput`.
+pub fn connect_timeout(addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    TcpStream::connect_timeout(&addr, timeout)
+}
+
+/// Resolves `host` and connects to `port` on the first address that
+/// accepts, failing with `TimedOut` if no connection is established within
+/// `timeout` overall.
+///
+/// This is a port of `network_connect` in
+/// `original/sysdeps/posix/network.cpp`. The error of the last address tried
+/// is returned, with the host and port added for context. A zero `timeout`
+/// is rejected with `InvalidInput`.
+pub fn network_connect_timeout(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    if timeout.is_zero() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "connect timeout must not be zero",
+        ));
+    }
+    let deadline = Instant::now() + timeout;
+    let addrs = (host, port).to_socket_addrs().map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to resolve host: '{}': {}", host, e),
+        )
+    })?;
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host resolved to no addresses");
+    for addr in addrs {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            last_error = io::ErrorKind::TimedOut.into();
+            break;
+        }
+        match TcpStream::connect_timeout(&addr, remaining) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(io::Error::new(
+        last_error.kind(),
+        format!("failed to connect to '{}:{}': {}", host, port, last_error),
+    ))
+}
+
+/// Puts `stream` in blocking or nonblocking mode.
+pub fn set_blocking(stream: &TcpStream, blocking: bool) -> io::Result<()> {
+    stream.set_nonblocking(!blocking)
+}
+
+/// Sets the `SO_SNDBUF` and `SO_RCVBUF` sizes of `stream`, leaving a size as
+/// is if it is `None`, e.g. to raise them for bulk transfers.
+///
+/// The OS may adjust the sizes; Linux doubles them to account for its
+/// bookkeeping, and clamps them to the system limits.
+pub fn set_socket_buffers(
+    stream: &TcpStream,
+    send: Option<usize>,
+    recv: Option<usize>,
+) -> io::Result<()> {
+    let socket = socket2::SockRef::from(stream);
+    if let Some(size) = send {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = recv {
+        socket.set_recv_buffer_size(size)?;
+    }
+    Ok(())
+}
+
+/// Sets `TCP_NODELAY` on `stream`, so that small packets, e.g. the
+/// keystrokes of an interactive shell, are sent right away.
+///
+/// This is a port of `disable_tcp_nagle` in `original/sysdeps.h`.
+pub fn disable_tcp_nagle(stream: &TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)
+}
+
+/// Makes `stream` send a keepalive probe after every `interval` of silence,
+/// dropping the connection after 10 unanswered probes. A `None` or zero
+/// `interval` disables keepalives.
+///
+/// This is a port of `set_tcp_keepalive` in `original/sysdeps_unix.cpp`. As
+/// in the C++ version, the probe count is fixed by the OS on Windows, where
+/// it is also 10.
+pub fn set_tcp_keepalive(stream: &TcpStream, interval: Option<Duration>) -> io::Result<()> {
+    let socket = socket2::SockRef::from(stream);
+    let interval = match interval {
+        Some(interval) if !interval.is_zero() => interval,
+        _ => return socket.set_keepalive(false),
+    };
+    let keepalive = socket2::TcpKeepalive::new().with_time(interval);
+    #[cfg(any(
+        target_os = "android",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        windows
+    ))]
+    let keepalive = keepalive.with_interval(interval);
+    #[cfg(any(
+        target_os = "android",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos"
+    ))]
+    let keepalive = keepalive.with_retries(10);
+    socket.set_tcp_keepalive(&keepalive)
+}
+
+/// Shuts down the read side, the write side, or both of a socket, like the
+/// C++ `adb_shutdown`.
+///
+/// Unlike `TcpStream::shutdown`, this takes any socket, e.g. a `UnixStream`
+/// from `socketpair` or a borrowed raw fd, so ported transport code can shut
+/// down whatever it holds the same way.
+#[cfg(unix)]
+pub fn shutdown<S: std::os::fd::AsFd>(socket: &S, how: Shutdown) -> io::Result<()> {
+    socket2::SockRef::from(socket).shutdown(how)
+}
+
+/// Shuts down the read side, the write side, or both of a socket, like the
+/// C++ `adb_shutdown`.
+///
+/// Unlike `TcpStream::shutdown`, this takes any socket, e.g. a borrowed raw
+/// socket, so ported transport code can shut down whatever it holds the same
+/// way.
+#[cfg(windows)]
+pub fn shutdown<S: std::os::windows::io::AsSocket>(socket: &S, how: Shutdown) -> io::Result<()> {
+    socket2::SockRef::from(socket).shutdown(how)
+}
+
+/// Returns the local address of `stream`, as given by `getsockname`.
+pub fn local_addr(stream: &TcpStream) -> io::Result<SocketAddr> {
+    stream.local_addr()
+}
+
+/// Returns the remote address of `stream`, as given by `getpeername`.
+pub fn peer_addr(stream: &TcpStream) -> io::Result<SocketAddr> {
+    stream.peer_addr()
+}
+
+/// Returns the addresses of the local network interfaces which are up,
+/// excluding loopback addresses, e.g. to advertise over mDNS.
+///
+/// Both IPv4 and IPv6 addresses are returned, including link-local ones. The
+/// result may be empty on a host without network connectivity.
+pub fn local_ip_addresses() -> io::Result<Vec<IpAddr>> {
+    let mut addrs = imp::interface_addresses()?;
+    addrs.retain(|addr| !addr.is_loopback());
+    Ok(addrs)
+}
+
+/// Connects to `port` on the loopback interface, trying IPv4 first and
+/// falling back to IPv6.
+///
+/// This is a port of `network_loopback_client` in
+/// `original/sysdeps/posix/network.cpp`.
+pub fn network_loopback_client(port: u16) -> io::Result<TcpStream> {
+    TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+        .or_else(|_| TcpStream::connect((Ipv6Addr::LOCALHOST, port)))
+}
+
+/// Listens on an ephemeral port of the IPv4 loopback interface, returning
+/// the listener and the port the OS picked.
+pub fn network_loopback_server_ephemeral() -> io::Result<(TcpListener, u16)> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let port = listener.local_addr()?.port();
+    Ok((listener, port))
+}
+
+/// Connects to `port` on the IPv6 loopback interface, `::1`.
+pub fn network_loopback_client_v6(port: u16) -> io::Result<TcpStream> {
+    TcpStream::connect((Ipv6Addr::LOCALHOST, port))
+}
+
+/// Listens on `port` of the IPv6 loopback interface, `::1`. A `port` of 0
+/// picks an ephemeral port.
+pub fn network_loopback_server_v6(port: u16) -> io::Result<TcpListener> {
+    TcpListener::bind((Ipv6Addr::LOCALHOST, port))
+}
+
+/// Listens on `port` of both the IPv4 and the IPv6 loopback interfaces,
+/// like adb on a dual-stack host, returning a listener for each family that
+/// could be bound.
+///
+/// A `port` of 0 picks an ephemeral port, which is the same for both
+/// families. Fails with the IPv6 error only if neither family could be
+/// bound, so this also works on a host with only one of them, e.g. an
+/// IPv6-only CI machine.
+pub fn network_loopback_server_dual(port: u16) -> io::Result<Vec<TcpListener>> {
+    let mut listeners = Vec::new();
+    let mut port = port;
+    if let Ok(listener) = TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        port = listener.local_addr()?.port();
+        listeners.push(listener);
+    }
+    match network_loopback_server_v6(port) {
+        Ok(listener) => listeners.push(listener),
+        Err(e) if listeners.is_empty() => return Err(e),
+        Err(_) => {}
+    }
+    Ok(listeners)
+}
+
+/// The stream type returned by `socketpair`.
+#[cfg(unix)]
+pub type LocalStream = std::os::unix::net::UnixStream;
+
+/// The stream type returned by `socketpair`.
+#[cfg(not(unix))]
+pub type LocalStream = TcpStream;
+
+/// Returns a connected pair of streams, e.g. to wire up a local transport in
+/// tests.
+///
+/// This is a port of `adb_socketpair` in `original/sysdeps_win32.cpp`. On
+/// Unix, this is a `UnixStream` pair. Elsewhere, it is a pair of TCP streams
+/// connected through a loopback listener, which is closed before returning.
+pub fn socketpair() -> io::Result<(LocalStream, LocalStream)> {
+    #[cfg(unix)]
+    return LocalStream::pair();
+
+    #[cfg(not(unix))]
+    {
+        let (listener, port) = network_loopback_server_ephemeral()?;
+        let client = network_loopback_client(port)?;
+        loop {
+            let (accepted, addr) = listener.accept()?;
+            // Another local process could connect to the listener in the
+            // meantime, so make sure the accepted stream is ours.
+            if addr == client.local_addr()? {
+                return Ok((client, accepted));
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::ptr;
+
+    pub fn interface_addresses() -> io::Result<Vec<IpAddr>> {
+        let mut ifaddrs = ptr::null_mut();
+        // SAFETY: ifaddrs is a valid out pointer.
+        if unsafe { libc::getifaddrs(&mut ifaddrs) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut addrs = Vec::new();
+        let mut cur = ifaddrs;
+        while !cur.is_null() {
+            // SAFETY: cur is a non-null entry of the list returned by
+            // getifaddrs, which is valid until freeifaddrs.
+            let ifa = unsafe { &*cur };
+            if ifa.ifa_flags & libc::IFF_UP as libc::c_uint != 0 {
+                // SAFETY: ifa_addr is either null or points to a sockaddr of
+                // the type given by its family.
+                if let Some(addr) = unsafe { to_ip_addr(ifa.ifa_addr) } {
+                    addrs.push(addr);
+                }
+            }
+            cur = ifa.ifa_next;
+        }
+
+        // SAFETY: ifaddrs was returned by getifaddrs, and isn't used again.
+        unsafe { libc::freeifaddrs(ifaddrs) };
+        Ok(addrs)
+    }
+
+    /// # Safety
+    ///
+    /// `addr` must be null or point to a valid sockaddr of the type given by
+    /// its `sa_family`.
+    unsafe fn to_ip_addr(addr: *const libc::sockaddr) -> Option<IpAddr> {
+        if addr.is_null() {
+            return None;
+        }
+        match (*addr).sa_family as libc::c_int {
+            libc::AF_INET => {
+                let addr = &*(addr as *const libc::sockaddr_in);
+                Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into())
+            }
+            libc::AF_INET6 => {
+                let addr = &*(addr as *const libc::sockaddr_in6);
+                Some(Ipv6Addr::from(addr.sin6_addr.s6_addr).into())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::ptr;
+    use windows_sys::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_SUCCESS};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER,
+        GAA_FLAG_SKIP_MULTICAST, IP_ADAPTER_ADDRESSES_LH,
+    };
+    use windows_sys::Win32::NetworkManagement::Ndis::IfOperStatusUp;
+    use windows_sys::Win32::Networking::WinSock::{
+        AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR, SOCKADDR_IN, SOCKADDR_IN6,
+    };
+
+    pub fn interface_addresses() -> io::Result<Vec<IpAddr>> {
+        let flags = GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER;
+        // The recommended initial size, which avoids calling twice in the
+        // common case.
+        let mut size: u32 = 15 * 1024;
+        let buf = loop {
+            // A Vec<u64> keeps the buffer aligned for the adapter structs.
+            let mut buf = vec![0u64; (size as usize).div_ceil(8)];
+            // SAFETY: buf is at least size bytes long.
+            let ret = unsafe {
+                GetAdaptersAddresses(
+                    AF_UNSPEC as u32,
+                    flags,
+                    ptr::null(),
+                    buf.as_mut_ptr().cast(),
+                    &mut size,
+                )
+            };
+            match ret {
+                ERROR_SUCCESS => break buf,
+                // size was updated to the required size.
+                ERROR_BUFFER_OVERFLOW => continue,
+                err => return Err(io::Error::from_raw_os_error(err as i32)),
+            }
+        };
+
+        let mut addrs = Vec::new();
+        let mut adapter = buf.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+        while !adapter.is_null() {
+            // SAFETY: adapter is a non-null entry of the list written to buf,
+            // which outlives this loop.
+            let info = unsafe { &*adapter };
+            if info.OperStatus == IfOperStatusUp {
+                let mut unicast = info.FirstUnicastAddress;
+                while !unicast.is_null() {
+                    // SAFETY: as above, for the adapter's address list.
+                    let address = unsafe { &*unicast };
+                    // SAFETY: lpSockaddr points to a sockaddr of the type
+                    // given by its family.
+                    if let Some(addr) = unsafe { to_ip_addr(address.Address.lpSockaddr) } {
+                        addrs.push(addr);
+                    }
+                    unicast = address.Next;
+                }
+            }
+            adapter = info.Next;
+        }
+        Ok(addrs)
+    }
+
+    /// # Safety
+    ///
+    /// `addr` must be null or point to a valid sockaddr of the type given by
+    /// its `sa_family`.
+    unsafe fn to_ip_addr(addr: *const SOCKADDR) -> Option<IpAddr> {
+        if addr.is_null() {
+            return None;
+        }
+        match (*addr).sa_family {
+            AF_INET => {
+                let addr = &*(addr as *const SOCKADDR_IN);
+                Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.S_un.S_addr)).into())
+            }
+            AF_INET6 => {
+                let addr = &*(addr as *const SOCKADDR_IN6);
+                Some(Ipv6Addr::from(addr.sin6_addr.u.Byte).into())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn connect_timeout_connects() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let stream =
+            connect_timeout(listener.local_addr().unwrap(), Duration::from_secs(5)).unwrap();
+        assert_eq!(listener.local_addr().unwrap(), stream.peer_addr().unwrap());
+    }
+
+    #[test]
+    fn local_and_peer_addr() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        assert_eq!(listener.local_addr().unwrap(), local_addr(&server).unwrap());
+        assert_eq!(listener.local_addr().unwrap(), peer_addr(&client).unwrap());
+        assert_eq!(local_addr(&client).unwrap(), peer_addr(&server).unwrap());
+    }
+
+    #[test]
+    fn connect_timeout_not_listening() {
+        // Find a port that nothing is listening on.
+        let addr = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let timeout = Duration::from_millis(500);
+        let start = Instant::now();
+        assert!(connect_timeout(addr, timeout).is_err());
+        // Either refused right away, or timed out, but never hangs.
+        assert!(start.elapsed() < timeout + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn connect_timeout_zero() {
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 1));
+        let err = connect_timeout(addr, Duration::ZERO).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn network_connect_timeout_connects() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = network_connect_timeout("127.0.0.1", port, Duration::from_secs(5)).unwrap();
+        assert_eq!(listener.local_addr().unwrap(), stream.peer_addr().unwrap());
+
+        let err = network_connect_timeout("127.0.0.1", port, Duration::ZERO).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    // Linux drops the SYN once a listener's accept queue is full, so connecting
+    // to a bound listener that never accepts times out rather than failing.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn network_connect_timeout_times_out() {
+        use std::os::unix::io::AsRawFd;
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        // Listening again only shrinks the backlog of the listening socket.
+        // SAFETY: listen has no memory safety requirements.
+        assert_eq!(0, unsafe { libc::listen(listener.as_raw_fd(), 0) });
+        let port = listener.local_addr().unwrap().port();
+
+        // Fill the accept queue.
+        let timeout = Duration::from_millis(300);
+        let mut queued = Vec::new();
+        let err = loop {
+            match network_connect_timeout("127.0.0.1", port, timeout) {
+                Ok(stream) => queued.push(stream),
+                Err(e) => break e,
+            }
+            assert!(queued.len() < 16, "accept queue never filled");
+        };
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+        let start = Instant::now();
+        let err = network_connect_timeout("127.0.0.1", port, timeout).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert!(start.elapsed() >= timeout);
+        assert!(start.elapsed() < timeout + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn set_blocking_toggles() {
+        use std::io::{Read, Write};
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+
+        set_blocking(&stream, false).unwrap();
+        let err = stream.read(&mut [0u8; 1]).unwrap_err();
+        assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+
+        // A blocking read waits for the data instead.
+        set_blocking(&stream, true).unwrap();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            server.write_all(b"x").unwrap();
+        });
+        let mut buf = [0u8; 1];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(b"x", &buf);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn set_socket_buffers_reads_back() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let socket = socket2::SockRef::from(&stream);
+        let recv = socket.recv_buffer_size().unwrap();
+
+        // The OS may round the size up, but not below what was asked for.
+        let size = 256 * 1024;
+        set_socket_buffers(&stream, Some(size), None).unwrap();
+        assert!(socket.send_buffer_size().unwrap() >= size);
+        assert_eq!(recv, socket.recv_buffer_size().unwrap());
+
+        set_socket_buffers(&stream, None, Some(size)).unwrap();
+        assert!(socket.recv_buffer_size().unwrap() >= size);
+    }
+
+    #[test]
+    fn loopback_server_ephemeral() {
+        let (listener, port) = network_loopback_server_ephemeral().unwrap();
+        assert_ne!(0, port);
+        assert_eq!(
+            SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+            listener.local_addr().unwrap()
+        );
+
+        let client = network_loopback_client(port).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        assert_eq!(client.local_addr().unwrap(), server.peer_addr().unwrap());
+    }
+
+    #[test]
+    fn loopback_v6() {
+        let listener = match network_loopback_server_v6(0) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("no IPv6 loopback ({}), skipping", e);
+                return;
+            }
+        };
+        let addr = listener.local_addr().unwrap();
+        assert_eq!(IpAddr::from(Ipv6Addr::LOCALHOST), addr.ip());
+
+        let client = network_loopback_client_v6(addr.port()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        assert_eq!(client.local_addr().unwrap(), server.peer_addr().unwrap());
+    }
+
+    #[test]
+    fn loopback_server_dual() {
+        let listeners = network_loopback_server_dual(0).unwrap();
+        assert!(!listeners.is_empty());
+        let port = listeners[0].local_addr().unwrap().port();
+        assert_ne!(0, port);
+        for listener in &listeners {
+            let addr = listener.local_addr().unwrap();
+            assert!(addr.ip().is_loopback());
+            assert_eq!(port, addr.port());
+            let client = TcpStream::connect(addr).unwrap();
+            let (server, _) = listener.accept().unwrap();
+            assert_eq!(client.local_addr().unwrap(), server.peer_addr().unwrap());
+        }
+    }
+
+    #[test]
+    fn disable_tcp_nagle_sets_nodelay() {
+        let (listener, port) = network_loopback_server_ephemeral().unwrap();
+        let stream = network_loopback_client(port).unwrap();
+        drop(listener);
+        stream.set_nodelay(false).unwrap();
+        disable_tcp_nagle(&stream).unwrap();
+        assert!(stream.nodelay().unwrap());
+    }
+
+    #[test]
+    fn set_tcp_keepalive_toggles() {
+        let (listener, port) = network_loopback_server_ephemeral().unwrap();
+        let stream = network_loopback_client(port).unwrap();
+        drop(listener);
+        let socket = socket2::SockRef::from(&stream);
+
+        set_tcp_keepalive(&stream, Some(Duration::from_secs(7))).unwrap();
+        assert!(socket.keepalive().unwrap());
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(Duration::from_secs(7), socket.keepalive_time().unwrap());
+            assert_eq!(Duration::from_secs(7), socket.keepalive_interval().unwrap());
+            assert_eq!(10, socket.keepalive_retries().unwrap());
+        }
+
+        set_tcp_keepalive(&stream, None).unwrap();
+        assert!(!socket.keepalive().unwrap());
+        set_tcp_keepalive(&stream, Some(Duration::ZERO)).unwrap();
+        assert!(!socket.keepalive().unwrap());
+    }
+
+    #[test]
+    fn shutdown_write_half() {
+        use std::io::{Read, Write};
+
+        let (listener, port) = network_loopback_server_ephemeral().unwrap();
+        let mut client = network_loopback_client(port).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+
+        client.write_all(b"bye").unwrap();
+        shutdown(&client, Shutdown::Write).unwrap();
+        let mut buf = Vec::new();
+        server.read_to_end(&mut buf).unwrap();
+        assert_eq!(b"bye", buf.as_slice());
+
+        // The read side of the client is still open.
+        server.write_all(b"ok").unwrap();
+        let mut buf = [0u8; 2];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(b"ok", &buf);
+    }
+
+    #[test]
+    fn shutdown_socketpair() {
+        use std::io::Read;
+
+        let (a, mut b) = socketpair().unwrap();
+        shutdown(&a, Shutdown::Both).unwrap();
+        assert_eq!(0, b.read(&mut [0u8; 1]).unwrap());
+    }
+
+    #[test]
+    fn local_ip_addresses_excludes_loopback() {
+        let addrs = local_ip_addresses().unwrap();
+        assert!(addrs.iter().all(|addr| !addr.is_loopback()));
+        if addrs.is_empty() {
+            // Nothing more to check without a network interface.
+            eprintln!("no non-loopback interface, skipping");
+            return;
+        }
+        // The addresses belong to this host, so they can be bound to. IPv6
+        // link-local addresses would need a scope id, so only check IPv4.
+        for addr in addrs.iter().filter(|addr| addr.is_ipv4()) {
+            TcpListener::bind((*addr, 0)).unwrap();
+        }
+    }
+
+    #[test]
+    fn socketpair_connected() {
+        use std::io::{Read, Write};
+
+        let (mut a, mut b) = socketpair().unwrap();
+        a.write_all(b"ping").unwrap();
+        let mut buf = [0u8; 4];
+        b.read_exact(&mut buf).unwrap();
+        assert_eq!(b"ping", &buf);
+
+        b.write_all(b"pong").unwrap();
+        a.read_exact(&mut buf).unwrap();
+        assert_eq!(b"pong", &buf);
+
+        drop(a);
+        assert_eq!(0, b.read(&mut buf).unwrap());
+    }
+}
@@ -0,0 +1,254 @@
+use std::io;
+
+/// A lightweight primitive for waking up another thread.
+///
+/// `notify` makes the notifier readable, so that a thread blocked in
+/// `fdevent` (or any other poller) on the notifier's fd wakes up. The woken
+/// thread calls `clear` to consume the notification. Notifications don't
+/// queue up: notifying several times before `clear` wakes the reader once.
+///
+/// This is backed by an `eventfd` on Linux, a self-pipe on other Unix
+/// systems, and a pair of loopback sockets on Windows.
+pub struct Notifier {
+    inner: imp::Notifier,
+}
+
+impl Notifier {
+    /// Creates a new notifier, which isn't readable until `notify` is called.
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            inner: imp::Notifier::new()?,
+        })
+    }
+
+    /// Makes the notifier readable. Notifying a notifier which is already
+    /// readable has no further effect.
+    pub fn notify(&self) -> io::Result<()> {
+        self.inner.notify()
+    }
+
+    /// Consumes any pending notification, so that the notifier is no longer
+    /// readable.
+    ///
+    /// Returns whether a notification was pending.
+    pub fn clear(&self) -> io::Result<bool> {
+        self.inner.clear()
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for Notifier {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawSocket for Notifier {
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.inner.as_raw_socket()
+    }
+}
+
+/// Reads from `reader` until it would block, returning whether anything was
+/// read.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn drain(mut reader: impl io::Read) -> io::Result<bool> {
+    let mut buf = [0u8; 64];
+    let mut drained = false;
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(drained),
+            Ok(_) => drained = true,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(drained),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Writes a single byte to `writer`. If the buffer is full, a notification
+/// is already pending, so that isn't an error.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn write_byte(mut writer: impl io::Write) -> io::Result<()> {
+    loop {
+        match writer.write(&[1]) {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+    pub struct Notifier {
+        eventfd: File,
+    }
+
+    impl Notifier {
+        pub fn new() -> io::Result<Self> {
+            // SAFETY: eventfd has no memory safety requirements.
+            let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+            if fd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: fd was just created, and nothing else owns it.
+            let eventfd = unsafe { File::from_raw_fd(fd) };
+            Ok(Self { eventfd })
+        }
+
+        pub fn notify(&self) -> io::Result<()> {
+            match (&self.eventfd).write(&1u64.to_ne_bytes()) {
+                // The counter would overflow, so it's definitely readable.
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+                result => result.map(|_| ()),
+            }
+        }
+
+        pub fn clear(&self) -> io::Result<bool> {
+            // A read returns and resets the whole counter.
+            let mut buf = [0u8; 8];
+            match (&self.eventfd).read(&mut buf) {
+                Ok(_) => Ok(true),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+
+        pub fn as_raw_fd(&self) -> RawFd {
+            self.eventfd.as_raw_fd()
+        }
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+mod imp {
+    use std::io::{self, PipeReader, PipeWriter};
+    use std::os::unix::io::{AsRawFd, RawFd};
+
+    pub struct Notifier {
+        reader: PipeReader,
+        writer: PipeWriter,
+    }
+
+    fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+        // SAFETY: fcntl has no memory safety requirements.
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    impl Notifier {
+        pub fn new() -> io::Result<Self> {
+            let (reader, writer) = io::pipe()?;
+            set_nonblocking(reader.as_raw_fd())?;
+            set_nonblocking(writer.as_raw_fd())?;
+            Ok(Self { reader, writer })
+        }
+
+        pub fn notify(&self) -> io::Result<()> {
+            super::write_byte(&self.writer)
+        }
+
+        pub fn clear(&self) -> io::Result<bool> {
+            super::drain(&self.reader)
+        }
+
+        pub fn as_raw_fd(&self) -> RawFd {
+            self.reader.as_raw_fd()
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::os::windows::io::{AsRawSocket, RawSocket};
+
+    pub struct Notifier {
+        reader: TcpStream,
+        writer: TcpStream,
+    }
+
+    impl Notifier {
+        pub fn new() -> io::Result<Self> {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+            let writer = TcpStream::connect(listener.local_addr()?)?;
+            let reader = loop {
+                let (stream, addr) = listener.accept()?;
+                // Ignore anything else that raced to connect to the listener.
+                if addr == writer.local_addr()? {
+                    break stream;
+                }
+            };
+            writer.set_nodelay(true)?;
+            reader.set_nonblocking(true)?;
+            writer.set_nonblocking(true)?;
+            Ok(Self { reader, writer })
+        }
+
+        pub fn notify(&self) -> io::Result<()> {
+            super::write_byte(&self.writer)
+        }
+
+        pub fn clear(&self) -> io::Result<bool> {
+            super::drain(&self.reader)
+        }
+
+        pub fn as_raw_socket(&self) -> RawSocket {
+            self.reader.as_raw_socket()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_and_clear() {
+        let notifier = Notifier::new().unwrap();
+        assert!(!notifier.clear().unwrap());
+
+        notifier.notify().unwrap();
+        notifier.notify().unwrap();
+        assert!(notifier.clear().unwrap());
+        assert!(!notifier.clear().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn notify_makes_fd_readable() {
+        use std::os::unix::io::AsRawFd;
+
+        let notifier = Notifier::new().unwrap();
+        let readable = || {
+            let mut pollfd = libc::pollfd {
+                fd: notifier.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: pollfd outlives the call.
+            let ret = unsafe { libc::poll(&mut pollfd, 1, 0) };
+            assert_ne!(-1, ret);
+            pollfd.revents & libc::POLLIN != 0
+        };
+
+        assert!(!readable());
+        notifier.notify().unwrap();
+        assert!(readable());
+        notifier.clear().unwrap();
+        assert!(!readable());
+    }
+}
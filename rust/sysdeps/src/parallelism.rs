@@ -0,0 +1,41 @@
+use std::env;
+use std::thread;
+
+/// Returns the number of threads to use for a worker pool.
+///
+/// A positive number in `$ADB_NUM_THREADS` takes precedence. Otherwise this
+/// is `std::thread::available_parallelism`, or 1 if that can't be
+/// determined.
+pub fn available_parallelism() -> usize {
+    parallelism_from(env::var("ADB_NUM_THREADS").ok().as_deref())
+}
+
+fn parallelism_from(num_threads: Option<&str>) -> usize {
+    num_threads
+        .and_then(|n| n.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_takes_precedence() {
+        assert_eq!(3, parallelism_from(Some("3")));
+        assert_eq!(64, parallelism_from(Some(" 64\n")));
+
+        let detected = parallelism_from(None);
+        assert!(detected >= 1);
+        for invalid in ["0", "-1", "many", ""] {
+            assert_eq!(detected, parallelism_from(Some(invalid)));
+        }
+
+        // No other test reads this variable, so setting it is safe.
+        env::set_var("ADB_NUM_THREADS", "5");
+        assert_eq!(5, available_parallelism());
+        env::remove_var("ADB_NUM_THREADS");
+        assert_eq!(detected, available_parallelism());
+    }
+}
@@ -0,0 +1,147 @@
+use std::io;
+
+/// Returns whether a process with the given `pid` exists.
+///
+/// On Unix, a process that has exited but hasn't been reaped by its parent
+/// yet (a zombie) is still considered to exist.
+pub fn process_exists(pid: u32) -> bool {
+    imp::process_exists(pid)
+}
+
+/// Asks the process with the given `pid` to terminate.
+///
+/// On Unix, this sends `SIGTERM`, and returns once the signal is sent. On
+/// Windows, the process is terminated immediately with `TerminateProcess`.
+pub fn terminate_process(pid: u32) -> io::Result<()> {
+    imp::terminate_process(pid)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+
+    fn to_pid(pid: u32) -> io::Result<libc::pid_t> {
+        // 0 and negative values have special meanings for kill(2), which
+        // would target process groups instead of a single process.
+        match libc::pid_t::try_from(pid) {
+            Ok(pid) if pid > 0 => Ok(pid),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid pid")),
+        }
+    }
+
+    pub fn process_exists(pid: u32) -> bool {
+        let Ok(pid) = to_pid(pid) else {
+            return false;
+        };
+        // SAFETY: kill has no memory safety requirements.
+        if unsafe { libc::kill(pid, 0) } == 0 {
+            return true;
+        }
+        // EPERM means the process exists, but we're not allowed to signal it.
+        io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+
+    pub fn terminate_process(pid: u32) -> io::Result<()> {
+        let pid = to_pid(pid)?;
+        // SAFETY: kill has no memory safety requirements.
+        if unsafe { libc::kill(pid, libc::SIGTERM) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::io;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, TerminateProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+        PROCESS_TERMINATE,
+    };
+
+    struct ProcessHandle(HANDLE);
+
+    impl ProcessHandle {
+        fn open(access: u32, pid: u32) -> io::Result<Self> {
+            // SAFETY: OpenProcess has no memory safety requirements.
+            let handle = unsafe { OpenProcess(access, 0, pid) };
+            if handle.is_null() {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Self(handle))
+            }
+        }
+    }
+
+    impl Drop for ProcessHandle {
+        fn drop(&mut self) {
+            // SAFETY: the handle is valid and owned by us.
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    pub fn process_exists(pid: u32) -> bool {
+        let Ok(handle) = ProcessHandle::open(PROCESS_QUERY_LIMITED_INFORMATION, pid) else {
+            return false;
+        };
+        let mut exit_code = 0;
+        // SAFETY: the handle is valid, and exit_code outlives the call.
+        if unsafe { GetExitCodeProcess(handle.0, &mut exit_code) } == 0 {
+            return false;
+        }
+        exit_code == STILL_ACTIVE as u32
+    }
+
+    pub fn terminate_process(pid: u32) -> io::Result<()> {
+        let handle = ProcessHandle::open(PROCESS_TERMINATE, pid)?;
+        // SAFETY: the handle is valid.
+        if unsafe { TerminateProcess(handle.0, 1) } == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    fn spawn_sleeper() -> std::process::Child {
+        #[cfg(unix)]
+        let mut command = Command::new("sleep");
+        #[cfg(unix)]
+        command.arg("60");
+        #[cfg(windows)]
+        let mut command = Command::new("ping");
+        #[cfg(windows)]
+        command.args(["-n", "60", "127.0.0.1"]);
+        command.stdout(Stdio::null()).spawn().unwrap()
+    }
+
+    #[test]
+    fn terminate_child() {
+        let mut child = spawn_sleeper();
+        let pid = child.id();
+        assert!(process_exists(pid));
+
+        terminate_process(pid).unwrap();
+        // Reap the child so that it doesn't linger as a zombie.
+        child.wait().unwrap();
+        assert!(!process_exists(pid));
+    }
+
+    #[test]
+    fn current_process_exists() {
+        assert!(process_exists(std::process::id()));
+    }
+
+    #[test]
+    fn invalid_pid() {
+        assert!(!process_exists(0));
+        assert!(terminate_process(0).is_err());
+    }
+}
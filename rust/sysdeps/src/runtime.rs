@@ -0,0 +1,89 @@
+use crate::process_exists;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns a path for a Unix domain socket or lock file named `name`, which
+/// is unique to the current process.
+///
+/// The path is in `$XDG_RUNTIME_DIR` if it's set to an existing directory,
+/// and in the temp directory otherwise, and is named `<name>.<pid>`. Calling
+/// this again with the same `name` in the same process returns the same
+/// path, which is not removed; the caller owns it.
+///
+/// Paths left behind for the same `name` by processes that no longer exist
+/// are removed, so that they don't accumulate after crashes.
+pub fn runtime_socket_path(name: &str) -> PathBuf {
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(env::temp_dir);
+    runtime_socket_path_in(&dir, name)
+}
+
+fn runtime_socket_path_in(dir: &Path, name: &str) -> PathBuf {
+    let pid = std::process::id();
+    remove_stale_paths(dir, name, pid);
+    dir.join(format!("{}.{}", name, pid))
+}
+
+/// Removes the `<name>.<pid>` entries in `dir` whose process no longer
+/// exists. Errors are ignored, since the cleanup is best effort.
+fn remove_stale_paths(dir: &Path, name: &str, own_pid: u32) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let prefix = format!("{}.", name);
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(pid) = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid != own_pid && !process_exists(pid) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runtime_socket_path_is_per_process() {
+        let path = runtime_socket_path("adb-test");
+        let file_name = format!("adb-test.{}", std::process::id());
+        assert_eq!(Some(file_name.as_ref()), path.file_name());
+        assert!(path.parent().unwrap().is_dir());
+
+        // The same name gives the same path within a process.
+        assert_eq!(path, runtime_socket_path("adb-test"));
+        assert_ne!(path, runtime_socket_path("adb-test-other"));
+    }
+
+    #[test]
+    fn removes_stale_paths() {
+        let dir = env::temp_dir().join(format!("sysdeps-runtime-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+
+        // A pid which is valid but above any realistic pid_max.
+        let stale = dir.join("adb.2147483646");
+        let live = dir.join(format!("adb.{}", std::process::id()));
+        let unrelated = dir.join("adbd.2147483646");
+        for path in [&stale, &live, &unrelated] {
+            fs::write(path, "").unwrap();
+        }
+
+        assert_eq!(live, runtime_socket_path_in(&dir, "adb"));
+        assert!(!stale.exists());
+        assert!(live.exists());
+        assert!(unrelated.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
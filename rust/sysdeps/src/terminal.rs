@@ -0,0 +1,103 @@
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+#[cfg(windows)]
+use std::os::windows::io::RawHandle;
+
+/// Returns whether `fd` refers to a terminal.
+#[cfg(unix)]
+pub fn is_terminal(fd: RawFd) -> bool {
+    // SAFETY: isatty has no memory safety requirements.
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+/// Returns whether `handle` refers to a console.
+#[cfg(windows)]
+// The handle is only passed to the OS, which validates it.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn is_terminal(handle: RawHandle) -> bool {
+    use windows_sys::Win32::System::Console::GetConsoleMode;
+
+    let mut mode = 0;
+    // SAFETY: mode outlives the call, and an invalid handle is reported as
+    // an error.
+    unsafe { GetConsoleMode(handle, &mut mode) != 0 }
+}
+
+/// Returns the size of the terminal attached to stdout as `(columns, rows)`,
+/// or `None` if stdout is not a terminal.
+///
+/// This is what the shell protocol sends to the device as the window size.
+#[cfg(unix)]
+pub fn terminal_size() -> Option<(u16, u16)> {
+    let mut ws = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ writes a winsize, which outlives the call.
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == -1 {
+        return None;
+    }
+    Some((ws.ws_col, ws.ws_row))
+}
+
+/// Returns the size of the console attached to stdout as `(columns, rows)`,
+/// or `None` if stdout is not a console.
+///
+/// This is what the shell protocol sends to the device as the window size.
+#[cfg(windows)]
+pub fn terminal_size() -> Option<(u16, u16)> {
+    use windows_sys::Win32::System::Console::{
+        GetConsoleScreenBufferInfo, GetStdHandle, CONSOLE_SCREEN_BUFFER_INFO, STD_OUTPUT_HANDLE,
+    };
+
+    // SAFETY: GetStdHandle has no memory safety requirements, and info
+    // outlives the call to GetConsoleScreenBufferInfo.
+    unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut info: CONSOLE_SCREEN_BUFFER_INFO = std::mem::zeroed();
+        if GetConsoleScreenBufferInfo(handle, &mut info) == 0 {
+            return None;
+        }
+        let window = info.srWindow;
+        Some((
+            (window.Right - window.Left + 1) as u16,
+            (window.Bottom - window.Top + 1) as u16,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_is_not_terminal() {
+        use std::os::unix::io::AsRawFd;
+
+        let (reader, writer) = std::io::pipe().unwrap();
+        assert!(!is_terminal(reader.as_raw_fd()));
+        assert!(!is_terminal(writer.as_raw_fd()));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn pipe_is_not_terminal() {
+        use std::os::windows::io::AsRawHandle;
+
+        let (reader, writer) = std::io::pipe().unwrap();
+        assert!(!is_terminal(reader.as_raw_handle()));
+        assert!(!is_terminal(writer.as_raw_handle()));
+    }
+
+    #[test]
+    fn terminal_size_matches_is_terminal() {
+        use std::io::IsTerminal;
+
+        if !std::io::stdout().is_terminal() {
+            assert_eq!(None, terminal_size());
+        }
+    }
+}
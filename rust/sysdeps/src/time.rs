@@ -0,0 +1,48 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the current wall-clock time.
+///
+/// Unlike `Instant`, this can jump when the system clock is changed, so it
+/// should only be used for timestamps that are sent over the wire or
+/// logged, and never to measure durations.
+pub fn wall_time() -> SystemTime {
+    SystemTime::now()
+}
+
+/// Returns the current wall-clock time in milliseconds since the Unix epoch,
+/// as used for e.g. sync mtimes and log timestamps.
+///
+/// A clock set before the epoch is reported as 0.
+pub fn unix_millis() -> u64 {
+    wall_time()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn unix_millis_is_now() {
+        let before = SystemTime::now();
+        let millis = unix_millis();
+        let after = SystemTime::now();
+
+        let millis = UNIX_EPOCH + Duration::from_millis(millis);
+        // Truncation to milliseconds can put the value just before `before`.
+        assert!(millis + Duration::from_millis(1) >= before);
+        assert!(millis <= after);
+        // 2020-01-01, to catch a value in the wrong unit.
+        assert!(millis > UNIX_EPOCH + Duration::from_secs(1_577_836_800));
+    }
+
+    #[test]
+    fn wall_time_is_now() {
+        let before = SystemTime::now();
+        let now = wall_time();
+        assert!(now >= before);
+        assert!(now <= SystemTime::now());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::trace_builder;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+static CAPTURE_LOGGER: OnceLock<CaptureLogger> = OnceLock::new();
+
+/// Captures the log lines emitted on the current thread, until dropped.
+///
+/// Created by `test_capture`. The guard is tied to the thread it was created
+/// on, so tests running in parallel don't see each other's lines.
+pub struct CaptureGuard {
+    // Capturing is per-thread, so the guard must not move to another thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl CaptureGuard {
+    /// Returns the lines captured so far, oldest first, formatted as
+    /// `"{LEVEL} {target}: {message}"`.
+    pub fn lines(&self) -> Vec<String> {
+        CAPTURED.with(|captured| captured.borrow().clone().unwrap_or_default())
+    }
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        CAPTURED.with(|captured| *captured.borrow_mut() = None);
+    }
+}
+
+/// Starts capturing the log lines emitted on the current thread, so that a
+/// test can assert that a line was logged.
+///
+/// `env_logger` can only be installed once per process, so this installs a
+/// logger of its own the first time it's called, which captures into a
+/// thread-local buffer and forwards to `env_logger` as configured by
+/// `ADB_TRACE`. Tests should call this instead of `adb_trace_init`. If a
+/// different logger has already been installed, nothing is captured.
+///
+/// ```
+/// let capture = trace::test_capture();
+/// log::warn!(target: "adb", "careful");
+/// assert_eq!(vec!["WARN adb: careful".to_string()], capture.lines());
+/// ```
+pub fn test_capture() -> CaptureGuard {
+    let mut installed = false;
+    let logger = CAPTURE_LOGGER.get_or_init(|| {
+        installed = true;
+        CaptureLogger {
+            inner: trace_builder().map(|mut builder| builder.build()),
+        }
+    });
+    if installed && log::set_logger(logger).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+    }
+    CAPTURED.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+    CaptureGuard {
+        _not_send: PhantomData,
+    }
+}
+
+/// A logger that records lines into the current thread's capture buffer, if
+/// any, and forwards the records enabled by `ADB_TRACE` to `env_logger`.
+struct CaptureLogger {
+    inner: Option<env_logger::Logger>,
+}
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        CAPTURED.with(|captured| {
+            if let Some(lines) = captured.borrow_mut().as_mut() {
+                lines.push(format!(
+                    "{} {}: {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ));
+            }
+        });
+        if let Some(inner) = &self.inner {
+            if inner.matches(record) {
+                inner.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{log_startup_banner, trace_builder_with, AdbTrace};
+use log::LevelFilter;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Initializes the tracing system like `adb_trace_init`, additionally
+/// enabling the tags listed in the config file at `path`.
+///
+/// Each line of the file is a `tag = level` pair, where the level is one of
+/// `off`, `error`, `warn`, `info`, `debug` or `trace`. Blank lines and lines
+/// starting with `#` are ignored:
+///
+/// ```text
+/// # Trace the transport, but only show warnings from the sockets.
+/// transport = trace
+/// sockets = warn
+/// ```
+///
+/// Tags enabled by `ADB_TRACE` are traced regardless of the file. Fails with
+/// `InvalidData` if a line is malformed or names an unknown tag or level, in
+/// which case the tracing system is left uninitialized.
+pub fn adb_trace_init_from_file(path: &Path) -> io::Result<()> {
+    let filters = parse_config(&fs::read_to_string(path)?)?;
+    if let Some(mut builder) = trace_builder_with(filters) {
+        builder.try_init().ok();
+        log_startup_banner();
+    }
+    Ok(())
+}
+
+fn parse_config(config: &str) -> io::Result<Vec<(AdbTrace, LevelFilter)>> {
+    let mut filters = Vec::new();
+    for (i, line) in config.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}: {:?}", i + 1, reason, line),
+            )
+        };
+        let (tag, level) = line
+            .split_once('=')
+            .ok_or_else(|| invalid("expected `tag = level`"))?;
+        let tag = AdbTrace::from_str(tag.trim()).ok_or_else(|| invalid("unknown tag"))?;
+        let level = level.trim().parse().map_err(|_| invalid("unknown level"))?;
+        filters.push((tag, level));
+    }
+    Ok(filters)
+}
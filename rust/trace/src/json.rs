@@ -0,0 +1,41 @@
+use crate::{log_startup_banner, trace_builder};
+use env_logger::fmt::Formatter;
+use env_logger::Target;
+use log::Record;
+use std::io::{self, Write};
+
+/// Initializes the tracing system like `adb_trace_init`, but writes each
+/// record to stderr as a line of JSON, for ingestion by a log collector:
+///
+/// ```json
+/// {"level":"TRACE","msg":"closing socket 42","tag":"sockets","ts":"2023-10-02T17:35:12.345Z"}
+/// ```
+///
+/// The records are filtered by `ADB_TRACE` as usual.
+pub fn adb_trace_init_json() {
+    init_json(Target::Stderr);
+}
+
+/// Initializes the tracing system like `adb_trace_init_json`, writing the
+/// lines to `writer` instead of stderr, e.g. a file or a socket to the
+/// collector.
+pub fn adb_trace_init_json_to<W: Write + Send + 'static>(writer: W) {
+    init_json(Target::Pipe(Box::new(writer)));
+}
+
+fn init_json(target: Target) {
+    if let Some(mut builder) = trace_builder() {
+        builder.target(target).format(format_json).try_init().ok();
+        log_startup_banner();
+    }
+}
+
+fn format_json(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    let line = serde_json::json!({
+        "ts": buf.timestamp_millis().to_string(),
+        "level": record.level().as_str(),
+        "tag": record.target(),
+        "msg": record.args().to_string(),
+    });
+    writeln!(buf, "{}", line)
+}
@@ -23,6 +23,16 @@
 //! }
 //! ```
 //!
+//! Alternatively, the `adb_trace!` macro takes the `AdbTrace` tag itself,
+//! so that a misspelled tag is a compile error rather than a silently
+//! disabled trace:
+//!
+//! ```
+//! use trace::{adb_trace, AdbTrace};
+//!
+//! adb_trace!(AdbTrace::Sockets, "This is a sockets trace message");
+//! ```
+//!
 //! To enable tracing, set the `ADB_TRACE` environment variable to a
 //! comma-separated list of tags. For example:
 //!
@@ -31,9 +41,35 @@
 //! ```
 //!
 //! The special values "1" and "all" can be used to enable all traces.
+//!
+//! Where the environment is inconvenient to change, e.g. for a daemon, the
+//! tags can also be enabled from a config file with
+//! `adb_trace_init_from_file()`.
+
+mod capture;
+mod config;
+mod json;
+mod ring_buffer;
+
+pub use capture::{test_capture, CaptureGuard};
+pub use config::adb_trace_init_from_file;
+pub use json::{adb_trace_init_json, adb_trace_init_json_to};
+pub use ring_buffer::TraceRingBuffer;
+
+#[doc(hidden)]
+pub use log as __log;
 
 use log::LevelFilter;
+use ring_buffer::RingBufferLogger;
 use std::env;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// A bitmask of the tags enabled by `ADB_TRACE`, indexed by the `AdbTrace`
+/// discriminant.
+static ENABLED_TAGS: AtomicU32 = AtomicU32::new(0);
+
+/// Whether the startup banner listing the enabled tags has been logged.
+static BANNER_LOGGED: AtomicBool = AtomicBool::new(false);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AdbTrace {
@@ -77,6 +113,7 @@ impl AdbTrace {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "adb" => Some(AdbTrace::Adb),
@@ -121,32 +158,117 @@ impl AdbTrace {
     }
 }
 
+/// Logs a trace message with an `AdbTrace` tag as the target.
+///
+/// `adb_trace!(AdbTrace::Sockets, "closing {}", id)` is equivalent to
+/// `log::trace!(target: "sockets", "closing {}", id)`, except that the tag is
+/// checked at compile time.
+#[macro_export]
+macro_rules! adb_trace {
+    ($tag:expr, $($arg:tt)+) => {
+        $crate::__log::trace!(target: $crate::AdbTrace::as_str(&$tag), $($arg)+)
+    };
+}
+
+/// Returns whether `tag` was enabled by `ADB_TRACE` when the tracing system
+/// was initialized, e.g. to skip formatting an expensive trace message.
+///
+/// This is a port of `VLOG_IS_ON` in `original/adb_trace.h`. It returns
+/// `false` for every tag before initialization.
+pub fn is_tag_enabled(tag: AdbTrace) -> bool {
+    ENABLED_TAGS.load(Ordering::Relaxed) & (1 << tag as u32) != 0
+}
+
 /// Initializes the tracing system.
 ///
 /// This function reads the `ADB_TRACE` environment variable and configures
 /// the `env_logger` backend to show trace messages for the specified tags.
+/// If any tags are enabled, the first initialization logs them, e.g.
+/// `tracing enabled: sockets, transport`.
 pub fn adb_trace_init() {
-    let trace_setting = env::var("ADB_TRACE").unwrap_or_default();
-    if trace_setting.is_empty() {
+    if let Some(mut builder) = trace_builder() {
+        builder.try_init().ok();
+        log_startup_banner();
+    }
+}
+
+/// Initializes the tracing system like `adb_trace_init`, and additionally
+/// retains the most recent `capacity` log lines in memory.
+///
+/// All records are retained in the returned ring buffer, regardless of
+/// whether they were enabled for output by `ADB_TRACE`. This allows dumping
+/// the recent history when an error occurs.
+///
+/// If a logger has already been installed, the returned ring buffer will stay
+/// empty.
+pub fn adb_trace_init_with_buffer(capacity: usize) -> TraceRingBuffer {
+    let ring = TraceRingBuffer::new(capacity);
+    let logger = RingBufferLogger {
+        inner: trace_builder().map(|mut builder| builder.build()),
+        ring: ring.clone(),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+    }
+    log_startup_banner();
+    ring
+}
+
+/// Logs which tags are enabled, once per process and only if any are, so
+/// that a user can check that `ADB_TRACE` was parsed as intended, e.g. that
+/// a misspelled tag didn't leave nothing enabled.
+fn log_startup_banner() {
+    let tags: Vec<_> = AdbTrace::all_tags()
+        .into_iter()
+        .filter(|tag| is_tag_enabled(*tag))
+        .map(|tag| tag.as_str())
+        .collect();
+    if tags.is_empty() || BANNER_LOGGED.swap(true, Ordering::Relaxed) {
         return;
     }
+    log::info!(target: "adb", "tracing enabled: {}", tags.join(", "));
+}
+
+/// Creates an `env_logger` builder configured from the `ADB_TRACE`
+/// environment variable, or `None` if tracing is disabled.
+fn trace_builder() -> Option<env_logger::Builder> {
+    trace_builder_with(Vec::new())
+}
+
+/// Creates an `env_logger` builder like `trace_builder`, additionally
+/// applying the per-tag `filters`. Tags enabled by `ADB_TRACE` take
+/// precedence over `filters`.
+fn trace_builder_with(filters: Vec<(AdbTrace, LevelFilter)>) -> Option<env_logger::Builder> {
+    let trace_setting = env::var("ADB_TRACE").unwrap_or_default();
+    if trace_setting.is_empty() && filters.is_empty() {
+        return None;
+    }
 
     let mut builder = env_logger::Builder::new();
     builder.filter(None, LevelFilter::Info); // Default level
 
-    let tags = trace_setting.split(|c| c == ',' || c == ' ').collect::<Vec<_>>();
+    let tags = trace_setting.split([',', ' ']).collect::<Vec<_>>();
 
-    if tags.contains(&"1") || tags.contains(&"all") {
-        for tag in AdbTrace::all_tags() {
-            builder.filter(Some(tag.as_str()), LevelFilter::Trace);
-        }
+    let enabled = if tags.contains(&"1") || tags.contains(&"all") {
+        AdbTrace::all_tags()
     } else {
-        for tag_str in tags {
-            if let Some(tag) = AdbTrace::from_str(tag_str) {
-                builder.filter(Some(tag.as_str()), LevelFilter::Trace);
+        tags.into_iter().filter_map(AdbTrace::from_str).collect()
+    };
+
+    let mut mask = 0;
+    for (tag, level) in filters {
+        if !enabled.contains(&tag) {
+            builder.filter(Some(tag.as_str()), level);
+            if level == LevelFilter::Trace {
+                mask |= 1 << tag as u32;
             }
         }
     }
+    for tag in enabled {
+        builder.filter(Some(tag.as_str()), LevelFilter::Trace);
+        mask |= 1 << tag as u32;
+    }
+    ENABLED_TAGS.store(mask, Ordering::Relaxed);
 
-    builder.try_init().ok();
+    Some(builder)
 }
@@ -0,0 +1,73 @@
+use log::{Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// A bounded, thread-safe buffer of the most recent log lines.
+///
+/// Created by `adb_trace_init_with_buffer`. Cloning a `TraceRingBuffer`
+/// returns a handle to the same underlying buffer.
+#[derive(Debug, Clone)]
+pub struct TraceRingBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl TraceRingBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Returns the most recent `n` lines, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        let skip = lines.len().saturating_sub(n);
+        lines.iter().skip(skip).cloned().collect()
+    }
+
+    fn push(&self, line: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// A logger that records every line into a `TraceRingBuffer`, and forwards
+/// the records enabled by `ADB_TRACE` to `env_logger`.
+pub(crate) struct RingBufferLogger {
+    pub(crate) inner: Option<env_logger::Logger>,
+    pub(crate) ring: TraceRingBuffer,
+}
+
+impl Log for RingBufferLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.ring.push(format!(
+            "{} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+        if let Some(inner) = &self.inner {
+            if inner.matches(record) {
+                inner.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
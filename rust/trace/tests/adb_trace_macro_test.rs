@@ -0,0 +1,16 @@
+use trace::{adb_trace, test_capture, AdbTrace};
+
+#[test]
+fn adb_trace_uses_tag_as_target() {
+    let capture = test_capture();
+    let id = 42;
+    adb_trace!(AdbTrace::Sockets, "closing socket {}", id);
+    adb_trace!(AdbTrace::MdnsStack, "resolved");
+    assert_eq!(
+        vec![
+            "TRACE sockets: closing socket 42".to_string(),
+            "TRACE mdns_stack: resolved".to_string(),
+        ],
+        capture.lines()
+    );
+}
@@ -0,0 +1,23 @@
+use log::{info, warn};
+use std::thread;
+use trace::test_capture;
+
+#[test]
+fn capture_warning() {
+    let capture = test_capture();
+    warn!(target: "transport", "connection reset");
+    assert_eq!(
+        vec!["WARN transport: connection reset".to_string()],
+        capture.lines()
+    );
+
+    // Lines logged on other threads aren't captured.
+    thread::spawn(|| info!(target: "adb", "elsewhere"))
+        .join()
+        .unwrap();
+    assert_eq!(1, capture.lines().len());
+
+    drop(capture);
+    let capture = test_capture();
+    assert!(capture.lines().is_empty());
+}
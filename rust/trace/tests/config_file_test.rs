@@ -0,0 +1,24 @@
+use std::{env, fs};
+use trace::{adb_trace_init_from_file, is_tag_enabled, AdbTrace};
+
+#[test]
+fn enables_tags_from_file() {
+    let path = env::temp_dir().join(format!("adb_trace_config_{}", std::process::id()));
+    fs::write(
+        &path,
+        "# Comments and blank lines are ignored.\n\ntransport = trace\nsockets = warn\n",
+    )
+    .unwrap();
+    env::set_var("ADB_TRACE", "sockets");
+    let result = adb_trace_init_from_file(&path);
+    fs::remove_file(&path).unwrap();
+    result.unwrap();
+
+    assert!(is_tag_enabled(AdbTrace::Transport));
+    assert!(log::log_enabled!(target: "transport", log::Level::Trace));
+    // ADB_TRACE wins over the file.
+    assert!(is_tag_enabled(AdbTrace::Sockets));
+    assert!(log::log_enabled!(target: "sockets", log::Level::Trace));
+    assert!(!is_tag_enabled(AdbTrace::Usb));
+    assert!(!log::log_enabled!(target: "usb", log::Level::Trace));
+}
@@ -0,0 +1,14 @@
+use std::env;
+use trace::{adb_trace_init, is_tag_enabled, AdbTrace};
+
+#[test]
+fn only_enabled_tags() {
+    assert!(!is_tag_enabled(AdbTrace::Sockets));
+
+    env::set_var("ADB_TRACE", "sockets,bogus");
+    adb_trace_init();
+
+    for tag in AdbTrace::all_tags() {
+        assert_eq!(tag == AdbTrace::Sockets, is_tag_enabled(tag), "{:?}", tag);
+    }
+}
@@ -0,0 +1,44 @@
+use log::trace;
+use std::env;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use trace::adb_trace_init_json_to;
+
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn json_lines() {
+    env::set_var("ADB_TRACE", "sockets");
+    let buf = SharedBuf::default();
+    adb_trace_init_json_to(buf.clone());
+
+    trace!(target: "sockets", "closing \"socket\" {}", 42);
+    trace!(target: "usb", "filtered out");
+
+    let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(2, lines.len(), "{}", output);
+
+    // The startup banner is logged like with the other backends.
+    let banner: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!("INFO", banner["level"]);
+    assert_eq!("adb", banner["tag"]);
+    assert_eq!("tracing enabled: sockets", banner["msg"]);
+
+    let value: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+    assert_eq!("TRACE", value["level"]);
+    assert_eq!("sockets", value["tag"]);
+    assert_eq!("closing \"socket\" 42", value["msg"]);
+    assert!(value["ts"].as_str().unwrap().ends_with('Z'));
+}
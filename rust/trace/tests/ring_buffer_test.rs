@@ -0,0 +1,23 @@
+use log::{info, trace};
+use trace::adb_trace_init_with_buffer;
+
+#[test]
+fn ring_buffer_retains_recent_lines() {
+    let ring = adb_trace_init_with_buffer(3);
+
+    for i in 0..5 {
+        trace!(target: "sockets", "line {}", i);
+    }
+    info!(target: "adb", "last");
+
+    assert_eq!(
+        vec![
+            "TRACE sockets: line 3".to_string(),
+            "TRACE sockets: line 4".to_string(),
+            "INFO adb: last".to_string(),
+        ],
+        ring.recent(10)
+    );
+    assert_eq!(vec!["INFO adb: last".to_string()], ring.recent(1));
+    assert!(ring.recent(0).is_empty());
+}
@@ -0,0 +1,18 @@
+use std::env;
+use trace::{adb_trace_init, test_capture};
+
+#[test]
+fn banner_lists_enabled_tags() {
+    env::set_var("ADB_TRACE", "transport,bogus,sockets");
+    // The capture logger is installed first, so the banner goes to it.
+    let capture = test_capture();
+    adb_trace_init();
+    assert_eq!(
+        vec!["INFO adb: tracing enabled: sockets, transport".to_string()],
+        capture.lines()
+    );
+
+    // The banner is only logged once.
+    adb_trace_init();
+    assert_eq!(1, capture.lines().len());
+}